@@ -0,0 +1,78 @@
+/* aiven_gatekeeper--1.0.sql */
+
+-- complain if script is sourced in psql, rather than via CREATE EXTENSION
+\echo Use "CREATE EXTENSION aiven_gatekeeper" to load this file. \quit
+
+-- reload the configuration and return the reserved roles in effect after the reload
+CREATE FUNCTION aiven_gatekeeper_reload()
+RETURNS text[]
+AS 'MODULE_PATHNAME', 'aiven_gatekeeper_reload'
+LANGUAGE C STRICT VOLATILE;
+
+REVOKE ALL ON FUNCTION aiven_gatekeeper_reload() FROM PUBLIC;
+
+-- report how our hooks are chained with the hooks of other preloaded libraries
+CREATE FUNCTION aiven_gatekeeper_hook_chain(
+    OUT process_utility_has_previous boolean,
+    OUT process_utility_installed_last boolean,
+    OUT executor_start_has_previous boolean,
+    OUT executor_start_installed_last boolean,
+    OUT object_access_has_previous boolean,
+    OUT object_access_installed_last boolean)
+RETURNS record
+AS 'MODULE_PATHNAME', 'aiven_gatekeeper_hook_chain'
+LANGUAGE C STRICT VOLATILE;
+
+REVOKE ALL ON FUNCTION aiven_gatekeeper_hook_chain() FROM PUBLIC;
+
+-- report the context the checks see for the current session
+CREATE FUNCTION aiven_gatekeeper_context(
+    OUT is_elevated boolean,
+    OUT is_security_restricted boolean,
+    OUT strict_mode boolean,
+    OUT agent_enabled boolean)
+RETURNS record
+AS 'MODULE_PATHNAME', 'aiven_gatekeeper_context'
+LANGUAGE C STRICT VOLATILE;
+
+REVOKE ALL ON FUNCTION aiven_gatekeeper_context() FROM PUBLIC;
+
+-- report how the checks classify a role
+CREATE FUNCTION aiven_gatekeeper_role_info(
+    rolename text,
+    OUT role_oid oid,
+    OUT is_reserved boolean,
+    OUT is_critical_login boolean,
+    OUT is_superuser boolean,
+    OUT is_restricted boolean)
+RETURNS record
+AS 'MODULE_PATHNAME', 'aiven_gatekeeper_role_info'
+LANGUAGE C STRICT VOLATILE;
+
+REVOKE ALL ON FUNCTION aiven_gatekeeper_role_info(text) FROM PUBLIC;
+
+-- report the entries of the list settings as the checks parse them
+CREATE FUNCTION aiven_gatekeeper_lists(
+    OUT list_name text,
+    OUT entry text)
+RETURNS SETOF record
+AS 'MODULE_PATHNAME', 'aiven_gatekeeper_lists'
+LANGUAGE C STRICT VOLATILE;
+
+REVOKE ALL ON FUNCTION aiven_gatekeeper_lists() FROM PUBLIC;
+
+-- report the statement node types the utility hook has a check for
+CREATE FUNCTION aiven_gatekeeper_covered_statements()
+RETURNS SETOF text
+AS 'MODULE_PATHNAME', 'aiven_gatekeeper_covered_statements'
+LANGUAGE C STRICT VOLATILE;
+
+REVOKE ALL ON FUNCTION aiven_gatekeeper_covered_statements() FROM PUBLIC;
+
+-- lift strict mode for the current session, with the break glass secret
+CREATE FUNCTION aiven_gatekeeper_break_glass(token text)
+RETURNS boolean
+AS 'MODULE_PATHNAME', 'aiven_gatekeeper_break_glass'
+LANGUAGE C STRICT VOLATILE;
+
+REVOKE ALL ON FUNCTION aiven_gatekeeper_break_glass(text) FROM PUBLIC;
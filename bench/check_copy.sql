@@ -0,0 +1,3 @@
+-- only the utility checks of COPY, the statement isn't executed
+SELECT aiven_gatekeeper_test_copy('bench', NULL, false, false);
+SELECT aiven_gatekeeper_test_copy('bench', NULL, true, false);
@@ -0,0 +1,3 @@
+-- only the utility checks of CREATE ROLE, the statement isn't executed
+SELECT aiven_gatekeeper_test_create_role('bench_role', 'createdb', NULL);
+SELECT aiven_gatekeeper_test_create_role('bench_role', 'superuser', 'false');
@@ -0,0 +1,54 @@
+#!/bin/sh
+# Runs the pgbench scripts in this directory with the agent off, on with the default lists
+# and on with long lists, and prints the average latency and tps of every run.
+# Needs a superuser connection (PGHOST, PGPORT, PGUSER) to a server running a
+# GATEKEEPER_TEST_API=1 build, eg: bench/run.sh defaultdb
+set -e
+
+db=${1:-postgres}
+duration=${BENCH_DURATION:-30}
+clients=${BENCH_CLIENTS:-4}
+dir=$(dirname "$0")
+
+# a few dozen reserved roles and allowed paths, the lists are scanned by most checks
+long_roles=$(seq -s, -f 'bench_reserved_%g' 1 40)
+long_paths=$(seq -s, -f '/var/lib/pgexport/bench_%g' 1 20)
+
+configure() {
+    psql -X -q -d "$db" -c "ALTER SYSTEM SET aiven.pg_security_agent = $1" \
+        -c "ALTER SYSTEM SET aiven.pg_security_agent_reserved_roles = '$2'" \
+        -c "ALTER SYSTEM SET aiven.pg_security_copy_allowed_paths = '$3'" \
+        -c "SELECT pg_reload_conf()" >/dev/null
+    sleep 1
+}
+
+run() {
+    pgbench -n -c "$clients" -T "$duration" -f "$dir/$2.sql" "$db" 2>/dev/null |
+        awk -v config="$1" -v script="$2" '
+            /^latency average/ { latency = $4 }
+            /^tps/ { tps = $3 }
+            END { printf "%-8s %-18s %10s ms %12s tps\n", config, script, latency, tps }'
+}
+
+psql -X -q -d "$db" -f "$dir/setup.sql"
+current_roles=$(psql -X -At -d "$db" -c "SHOW aiven.pg_security_agent_reserved_roles")
+
+configure off "$current_roles" ""
+run off utility
+run off select
+
+configure on "$current_roles" ""
+for script in utility select check_create_role check_copy; do
+    run default $script
+done
+
+configure on "$current_roles,$long_roles" "$long_paths"
+for script in utility select check_create_role check_copy; do
+    run long $script
+done
+
+# back to the settings of the configuration file
+psql -X -q -d "$db" -c "ALTER SYSTEM RESET aiven.pg_security_agent" \
+    -c "ALTER SYSTEM RESET aiven.pg_security_agent_reserved_roles" \
+    -c "ALTER SYSTEM RESET aiven.pg_security_copy_allowed_paths" \
+    -c "SELECT pg_reload_conf()" >/dev/null
@@ -0,0 +1,3 @@
+-- queries running through the executor start and object access hooks
+SELECT count(*) FROM bench;
+SELECT lower('BENCH'), now();
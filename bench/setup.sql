@@ -0,0 +1,6 @@
+-- objects used by the benchmark scripts, needs a build with make GATEKEEPER_TEST_API=1
+CREATE TABLE IF NOT EXISTS bench (id int);
+CREATE OR REPLACE FUNCTION aiven_gatekeeper_test_create_role(rolename text, option text, value text)
+RETURNS boolean AS 'aiven_gatekeeper', 'aiven_gatekeeper_test_create_role' LANGUAGE C;
+CREATE OR REPLACE FUNCTION aiven_gatekeeper_test_copy(relname text, filename text, is_from boolean, is_program boolean)
+RETURNS boolean AS 'aiven_gatekeeper', 'aiven_gatekeeper_test_copy' LANGUAGE C;
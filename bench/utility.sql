@@ -0,0 +1,5 @@
+-- utility statements running through the utility hook
+SET ROLE NONE;
+RESET ROLE;
+CREATE TEMP TABLE IF NOT EXISTS bench_temp (id int);
+TRUNCATE bench_temp;
@@ -0,0 +1,114 @@
+/* -------------------------------------------------------------------------
+ *
+ * list_parse_fuzz.c
+ *
+ * libFuzzer entry point for the list parsers in src/list_parse.c, see fuzz/Makefile.
+ * The first input byte picks the parser, the rest is the setting value.
+ *
+ * Copyright (c) 2022 Aiven, Helsinki, Finland. https://aiven.io/
+ *
+ * IDENTIFICATION
+ *		fuzz/list_parse_fuzz.c
+ *
+ * -------------------------------------------------------------------------
+ */
+#include "postgres.h"
+
+#include <stdint.h>
+
+#include "parser/scansup.h"
+
+#include "list_parse.h"
+
+int LLVMFuzzerTestOneInput(const uint8_t *data, size_t size);
+
+/* the server functions the parsers use, without a memory context */
+bool
+scanner_isspace(char ch)
+{
+    /* same as src/backend/parser/scansup.c of PostgreSQL 16 and later */
+    return ch == ' ' || ch == '\t' || ch == '\n' || ch == '\r' || ch == '\v' || ch == '\f';
+}
+
+char *
+pstrdup(const char *in)
+{
+    return strdup(in);
+}
+
+char *
+pnstrdup(const char *in, Size len)
+{
+    return strndup(in, len);
+}
+
+/* stripping keeps every non empty entry as it is, so running it on its own output
+ * changes nothing, and it never writes more than it was given
+ */
+static void
+fuzz_strip_empty_list_entries(const char *value)
+{
+    size_t len = strlen(value);
+    char *out = malloc(len + 1);
+    char *again = malloc(len + 1);
+
+    gatekeeper_strip_empty_list_entries(value, out);
+    if (strlen(out) > len)
+        abort();
+    if (out[0] == ',')
+        abort();
+
+    gatekeeper_strip_empty_list_entries(out, again);
+    if (strcmp(out, again) != 0)
+        abort();
+
+    free(again);
+    free(out);
+}
+
+/* an accepted entry is three non empty names without a dot, joined back it is the entry */
+static void
+fuzz_parse_sensitive_column(const char *value)
+{
+    char *schema;
+    char *table;
+    char *column;
+    size_t len;
+
+    if (!gatekeeper_parse_sensitive_column(value, &schema, &table, &column))
+        return;
+
+    if (schema[0] == '\0' || table[0] == '\0' || column[0] == '\0')
+        abort();
+    if (strchr(schema, '.') || strchr(table, '.') || strchr(column, '.'))
+        abort();
+    len = strlen(schema) + strlen(table) + strlen(column) + 2;
+    if (len != strlen(value) || strncmp(value, schema, strlen(schema)) != 0)
+        abort();
+
+    free(schema);
+    free(table);
+    free(column);
+}
+
+int
+LLVMFuzzerTestOneInput(const uint8_t *data, size_t size)
+{
+    char *value;
+
+    if (size < 1)
+        return 0;
+
+    /* the settings are NUL terminated strings, anything after an embedded NUL is ignored */
+    value = malloc(size);
+    memcpy(value, data + 1, size - 1);
+    value[size - 1] = '\0';
+
+    if (data[0] & 1)
+        fuzz_parse_sensitive_column(value);
+    else
+        fuzz_strip_empty_list_entries(value);
+
+    free(value);
+    return 0;
+}
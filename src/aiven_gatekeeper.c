@@ -11,28 +11,77 @@
  */
 #include "postgres.h"
 
+#include <ctype.h>
+#include <signal.h>
+
+#include "access/genam.h"
+#if PG12_GTE
+#include "access/table.h"
+#else
+#include "access/heapam.h"
+#define table_open(r, l) heap_open(r, l)
+#define table_close(r, l) heap_close(r, l)
+#endif
+#include "access/parallel.h"
 #include "access/xact.h"
+#include "catalog/catalog.h"
+#include "catalog/namespace.h"
 #include "catalog/objectaccess.h"
+#include "catalog/pg_authid.h"
+#include "catalog/pg_class.h"
+#include "catalog/pg_collation.h"
+#include "catalog/pg_extension.h"
+#include "catalog/pg_namespace.h"
+#include "commands/async.h"
 #include "commands/extension.h"
 #include "commands/defrem.h"
 #include "commands/explain.h"
 #include "executor/instrument.h"
+#include "foreign/foreign.h"
+#include "mb/pg_wchar.h"
+#include "nodes/makefuncs.h"
 #include "nodes/value.h"
 #include "fmgr.h"
+#include "libpq/crypt.h"
+#include "funcapi.h"
 #include "miscadmin.h"
+#include "parser/parse_func.h"
 #include "parser/parse_relation.h"
+#include "parser/parse_type.h"
+#include "parser/parsetree.h"
+#include "parser/scansup.h"
+#include "pgstat.h"
+#include "regex/regex.h"
+#include "postmaster/bgworker.h"
+#include "storage/fd.h"
+#include "storage/ipc.h"
+#include "storage/latch.h"
+#include "storage/lock.h"
+#include "storage/lwlock.h"
+#include "storage/proc.h"
+#include "storage/shmem.h"
 #include "tcop/utility.h"
 #include "utils/acl.h"
+#include "utils/array.h"
 #include "utils/builtins.h"
 #include "utils/guc.h"
+#include "utils/inval.h"
 #include "utils/fmgrtab.h"
+#include "utils/json.h"
 #include "utils/lsyscache.h"
 #include "utils/resowner.h"
+#include "utils/syscache.h"
+#include "utils/timeout.h"
+#include "utils/timestamp.h"
+#include "utils/tuplestore.h"
 #include "utils/varlena.h"
 #include "nodes/nodes.h"
 #include "access/sysattr.h"
+#include "catalog/pg_proc.h"
+#include "catalog/pg_type.h"
 
 #include "aiven_gatekeeper.h"
+#include "list_parse.h"
 
 PG_MODULE_MAGIC;
 
@@ -43,21 +92,139 @@ PG_MODULE_MAGIC;
 #define CURRENT_ROLE_IS_SUPERUSER session_auth_is_superuser
 #endif
 
+/* a monitor only build reports the denial and lets the statement continue */
+#if GATEKEEPER_MONITOR_ONLY
+#define DENY_ELEVEL WARNING
+#define DENY_ACTION "monitor"
+#else
+#define DENY_ELEVEL ERROR
+#define DENY_ACTION "deny"
+#endif
+
 void _PG_init(void);
 void _PG_fini(void);
+PGDLLEXPORT void aiven_gatekeeper_notify_main(Datum main_arg);
+PGDLLEXPORT void aiven_gatekeeper_metrics_main(Datum main_arg);
+PGDLLEXPORT void aiven_gatekeeper_reconcile_main(Datum main_arg);
 
 static bool is_elevated(void);
 static bool is_security_restricted(void);
+static bool is_reserved_caller(void);
+static bool is_current_user_reserved(void);
+static bool is_bypass_role(void);
+static void reserved_status_invalidate(Datum arg, int cacheid, uint32 hashvalue);
+static bool allow_superuser_role(const char *target_role);
+static char *reserved_roles_setting(void);
+static char *merge_reserved_roles_setting(const char *setting, const char *from_file);
+static List *resolve_reserved_roles(const char *setting);
+static List *get_reserved_roles(void);
+static int compile_role_regex(const char *pattern, regex_t *regex);
+static char *invalid_role_regex(List *elemlist);
+static void role_regex_cache_reset(void);
+static bool role_regex_matches(const char *pattern, const char *target_role);
+static char *rolespec_display_name(RoleSpec *role);
+static bool split_identifier_list(const char *value, List **elemlist, int *duplicates);
+static bool identifier_list_contains(const char *list_value, const char *name);
+static char *protected_relation_schema(RangeVar *relation);
+static char *resolve_copy_path(const char *path);
+static bool copy_path_allowed(const char *filename);
+static bool node_type_matches(Node *stmt, NodeTag expected, const char *check_name);
+static void alter_role_checks(Node *stmt);
+static void create_role_checks(Node *stmt);
+static void grant_role_checks(Node *stmt);
+static void reserved_name_checks(const char *statement_type, const char *rolename);
+static void role_name_policy_checks(const char *statement_type, const char *rolename);
+static void rename_role_checks(Node *stmt);
+static bool copy_strict_path_allowed(CopyStmt *copyStmt);
+static void copy_stmt_checks(Node *stmt);
+static void copy_catalog_checks(CopyStmt *copyStmt);
+static void create_function_checks(Node *stmt);
+static void do_stmt_checks(Node *stmt);
+static void create_trigger_checks(Node *stmt);
+static void alter_function_checks(Node *stmt);
+static void alter_owner_checks(Node *stmt);
+static void create_access_method_checks(Node *stmt);
+static bool is_protected_guc(const char *name);
+static void protected_guc_set_checks(const char *statement_type, const char *target, VariableSetStmt *setstmt);
+static void alter_database_set_checks(Node *stmt);
+static void alter_role_set_checks(Node *stmt);
+static void variable_set_checks(Node *stmt);
+static void lock_checks(Node *stmt);
+static void alter_system_checks(Node *stmt);
+static bool is_untrusted_language(const char *language);
+static void replaced_function_checks(CreateFunctionStmt *createFuncStmt);
+static bool reserved_bypass_allowed(const char *category);
+static bool extension_policy_allows(const char *policy, const char *extname);
+static void create_extension_checks(Node *stmt);
+static bool restricted_foreign_server(const char *servername);
+static void create_foreign_table_checks(Node *stmt);
+static void import_foreign_schema_checks(Node *stmt);
+static void truncate_checks(Node *stmt);
+static void alter_default_privileges_checks(Node *stmt);
+static void create_event_trigger_checks(Node *stmt);
+static void create_schema_checks(Node *stmt);
+static void alter_table_checks(Node *stmt);
+static void reassign_owned_checks(Node *stmt);
+static void drop_owned_checks(Node *stmt);
+static Oid gatekeeper_extension_schema(void);
+static void extension_drop_checks(const char *statement_type, const char *description, const char *audit_message);
+static void drop_stmt_checks(Node *stmt);
+static char *object_node_name(Node *object);
+static void security_label_checks(Node *stmt);
+static void rewrite_protected_table_checks(const char *statement_type, RangeVar *relation);
+static void rewrite_protected_database_checks(const char *statement_type);
+static void cluster_checks(Node *stmt);
+static void vacuum_checks(Node *stmt);
+static void register_named_check(NodeTag tag, const char *name, gatekeeper_check_fn check);
+static void register_default_checks(void);
+static const char *utility_statement_name(Node *stmt);
+static void default_deny_checks(Node *stmt);
+static void run_utility_checks(Node *stmt);
 static void gatekeeper_checks(PROCESS_UTILITY_PARAMS);
+static void verify_hook_chain(Node *stmt);
+static char *pending_config_value(const char *name);
+static char *read_reserved_roles_file(const char *path);
+static void report_hook_miss(const char *hook, const char *checks);
+static void run_previous_utility(PROCESS_UTILITY_PARAMS);
+#if GATEKEEPER_EXECUTOR_HOOK
+static void pg_proc_guard_checks(QueryDesc *queryDesc, int eflags);
+static void forced_timeout_executor_run(QueryDesc *queryDesc, ScanDirection direction, uint64 count, bool execute_once);
+#endif
+#if GATEKEEPER_OBJECT_ACCESS_HOOK
 static void gatekeeper_oa_hook(ObjectAccessType access,
                                Oid classId,
                                Oid objectId,
                                int subId,
                                void *arg);
-static char *allow_role_stmt(void);
+#endif
+static char *allow_role_stmt(const char *attribute);
+static const char *role_option_attribute(DefElem *defel);
+static const char *role_attribute_name(DefElem *defel);
 static void allow_granted_roles(List *addroleto);
 static char *allow_grant_or_alter_role(Oid role_oid);
+static bool is_restricted_role(Oid role_oid);
+static const char *server_access_role(Oid role_oid);
+static void server_access_grant_checks(const char *statement_type, Oid granted_oid, const char *granted_name, const char *grantee_name);
+static void admin_option_grant_checks(const char *statement_type, const char *granted_name, const char *grantee_name);
+static bool grant_role_admin_option(GrantRoleStmt *grantRoleStmt);
 static bool allowed_guc_change_check_hook(bool *newval, void **extra, GucSource source);
+static bool strict_runtime_check_hook(bool *newval, void **extra, GucSource source);
+static bool allowed_guc_change_int_check_hook(int *newval, void **extra, GucSource source);
+static void record_denial(const char *statement_type);
+static char *build_audit_json(const char *statement_type, const char *reason_code, const char *action, const char *message);
+static void queue_denial_notification(const char *statement_type, const char *reason_code, const char *action, const char *message);
+static void count_denial(const char *statement_type);
+static bool is_strict_mode_enabled(void);
+static const char *restricted_context_reason(void);
+static void gatekeeper_audit_log(int elevel, const char *statement_type, const char *reason_code, const char *action, const char *message);
+static void gatekeeper_deny(const char *statement_type, const char *reason_code, const char *fmt, ...) pg_attribute_printf(3, 4);
+static bool deny_unless_reserved(const char *statement_type, const char *fmt, ...) pg_attribute_printf(2, 3);
+static void gatekeeper_hard_deny(const char *statement_type, const char *reason_code, const char *message);
+#if !GATEKEEPER_MONITOR_ONLY
+static void raise_collected_denials(void);
+#endif
+static bool denials_pending(void);
+static void privileged_statement_checks(const char *statement_type, const char *description);
 
 /* disallow-list of reserved functions we don't want to give access to
  * as these can be abused in to get local filesystem access or as a step
@@ -75,7 +242,9 @@ static const char *reserved_func_names[] = {"pg_read_file",
                                             "be_lo_export",
                                             "be_lo_import_with_oid"};
 static const int NUM_RESERVED_FUNCS = sizeof reserved_func_names / sizeof reserved_func_names[0];
-static Oid *reserved_func_oids; // array to track the oids of the above reserved_func_names
+static const char **reserved_func_list; // reserved_func_names followed by aiven.pg_security_reserved_functions
+static Oid *reserved_func_oids;         // array to track the oids of the above reserved_func_list
+static int num_reserved_funcs = 0;
 static int max_reserved_oid = 0;
 static int min_reserved_oid = 9000;
 
@@ -87,21 +256,259 @@ static const int NUM_RESERVED_COLS = sizeof reserved_col_names / sizeof reserved
 static const char *reserved_auth_col_names[] = {"rolpassword"};
 static const int NUM_RESERVED_AUTH_COLS = sizeof reserved_auth_col_names / sizeof reserved_auth_col_names[0];
 
+/* statement types that never need a check, allowed even with default deny.
+ * Transaction control, session state, cursors and prepared statements, the
+ * statements they run are checked on their own.
+ */
+static const NodeTag default_allowed_statements[] = {T_TransactionStmt,
+                                                     T_VariableSetStmt,
+                                                     T_VariableShowStmt,
+                                                     T_ConstraintsSetStmt,
+                                                     T_DiscardStmt,
+                                                     T_ExplainStmt,
+                                                     T_PrepareStmt,
+                                                     T_ExecuteStmt,
+                                                     T_DeallocateStmt,
+                                                     T_DeclareCursorStmt,
+                                                     T_FetchStmt,
+                                                     T_ClosePortalStmt,
+                                                     T_CallStmt,
+                                                     T_ListenStmt,
+                                                     T_UnlistenStmt,
+                                                     T_NotifyStmt};
+static const int NUM_DEFAULT_ALLOWED_STATEMENTS = sizeof default_allowed_statements / sizeof default_allowed_statements[0];
+
+/* settings that load code or configure the agent, not allowed as per database or per role defaults.
+ * All aiven.* settings are protected as well, see is_protected_guc.
+ */
+static const char *protected_guc_names[] = {"session_preload_libraries",
+                                            "local_preload_libraries",
+                                            "shared_preload_libraries",
+                                            "dynamic_library_path"};
+static const int NUM_PROTECTED_GUCS = sizeof protected_guc_names / sizeof protected_guc_names[0];
+
+/* procedural languages that give access to the server, as the user running postgres */
+static const char *untrusted_languages[] = {"plperlu", "plpythonu", "plpython2u", "plpython3u", "pltclu"};
+static const int NUM_UNTRUSTED_LANGUAGES = sizeof untrusted_languages / sizeof untrusted_languages[0];
+
+/* format of the audit records written for denied statements */
+typedef enum
+{
+    AUDIT_FORMAT_TEXT,
+    AUDIT_FORMAT_JSON
+} AuditFormat;
+
+static const struct config_enum_entry audit_format_options[] = {
+    {"text", AUDIT_FORMAT_TEXT, false},
+    {"json", AUDIT_FORMAT_JSON, false},
+    {NULL, 0, false}};
+
+/* handling of utility statement types without a registered check */
+typedef enum
+{
+    DEFAULT_DENY_OFF,
+    DEFAULT_DENY_LOG,
+    DEFAULT_DENY_ON
+} DefaultDenyMode;
+
+static const struct config_enum_entry default_deny_options[] = {
+    {"off", DEFAULT_DENY_OFF, false},
+    {"log", DEFAULT_DENY_LOG, false},
+    {"on", DEFAULT_DENY_ON, false},
+    {NULL, 0, false}};
+
 /* GUC Variables */
 static bool pg_security_agent_enabled = false;
 static bool pg_security_agent_strict = false;
+static bool pg_security_agent_strict_runtime = false;
+/* secret for aiven_gatekeeper_break_glass(), and whether this session used it */
+static char *break_glass_secret = NULL;
+static bool break_glass_active = false;
+/* the reserved role used when aiven.pg_security_agent_reserved_roles isn't set,
+ * and the fallback when it is empty in strict mode
+ */
+#define DEFAULT_RESERVED_ROLE "postgres"
+
 static char *allowed_superuser_roles = NULL;
+static char *reserved_roles_file = NULL;
+/* the entries read from aiven.pg_security_config_file, owned by the GUC as its extra */
+static const char *reserved_roles_from_file = NULL;
+static int pg_security_audit_format = AUDIT_FORMAT_TEXT;
+static char *protected_schemas = NULL;
+static char *copy_allowed_paths = NULL;
+static char *sensitive_columns = NULL;
+static bool notice_when_disabled = false;
+static char *protected_role_attributes = NULL;
+static char *critical_login_roles = NULL;
+static char *guarded_role_attributes = NULL;
+static bool require_change_reason = false;
+static bool audit_allowed = false;
+static char *change_reason = NULL;
+static char *trace_id = NULL;
+#define MAX_TRACE_ID_LENGTH 128
+static bool block_security_definer = false;
+static bool copy_program_hard_block = false;
+static bool enforce_password_policy = false;
+static char *reserved_bypass = NULL;
+static char *bypass_roles = NULL;
+static char *role_name_pattern = NULL;
+static char *extension_policy = NULL;
+static int default_deny = DEFAULT_DENY_OFF;
+static int anomaly_threshold = 5;
+static int max_role_options = 100;
+
+/* whether the current user is one of the reserved roles, remembered for the role it
+ * was resolved for. Reset when the reserved roles are reloaded or pg_authid changes.
+ */
+static Oid reserved_status_roleid = InvalidOid;
+static bool reserved_status = false;
+
+/* compiled ~regex entries of the reserved roles, compiled on first use after a change */
+typedef struct
+{
+    char *pattern;
+    regex_t regex;
+} RoleRegex;
+
+static List *role_regex_cache = NIL;
+static int anomaly_window = 60;
+
+static bool denial_notify_enabled = false;
+static char *denial_notify_channel = NULL;
+static char *denial_notify_database = NULL;
+
+static bool metrics_enabled = false;
+static int metrics_interval = 60;
+static char *metrics_file = NULL;
+
+static char *extra_reserved_functions = NULL;
+
+static bool superuser_reconcile_enabled = false;
+static int superuser_reconcile_interval = 300;
+
+/* shared memory state, only available when loaded through shared_preload_libraries.
+ * Denials are put in a fixed-size queue, the notify worker sends them out as NOTIFY.
+ * When the queue is full the oldest entry is overwritten.
+ */
+#define DENIAL_QUEUE_SIZE 64
+#define DENIAL_PAYLOAD_LEN 2048
+
+/* denials since startup per statement type, for the metrics worker.
+ * Statement types beyond the available slots are only counted in the total.
+ */
+#define MAX_STATEMENT_METRICS 32
+
+typedef struct StatementMetric
+{
+    char statement_type[NAMEDATALEN];
+    uint64 denials;
+} StatementMetric;
+
+/* denials per role in the current anomaly window, shared by all backends so that
+ * reconnecting doesn't start the count over. Bounded, when all slots are in use
+ * the one with the oldest window is reused.
+ */
+#define MAX_DENIAL_COUNTERS 64
+
+typedef struct DenialCounter
+{
+    Oid roleid;
+    TimestampTz window_start;
+    int count;
+} DenialCounter;
+
+typedef struct GatekeeperSharedState
+{
+    LWLock *lock;
+    Latch *worker_latch;         // latch of the notify worker, NULL when not running
+    uint64 queue_head;           // next entry to write
+    uint64 queue_tail;           // next entry to send
+    uint64 queue_dropped;        // entries overwritten before they were sent
+    char queue[DENIAL_QUEUE_SIZE][DENIAL_PAYLOAD_LEN];
+    uint64 denials_total;        // all denials since startup
+    int num_metrics;             // statement types in use in metrics
+    StatementMetric metrics[MAX_STATEMENT_METRICS];
+    DenialCounter denial_counters[MAX_DENIAL_COUNTERS];
+} GatekeeperSharedState;
+
+static GatekeeperSharedState *gatekeeper_state = NULL;
+static shmem_startup_hook_type prev_shmem_startup_hook = NULL;
+#if PG15_GTE
+static shmem_request_hook_type prev_shmem_request_hook = NULL;
+#endif
+
+static volatile sig_atomic_t notify_worker_got_sighup = false;
+static volatile sig_atomic_t metrics_worker_got_sighup = false;
+static volatile sig_atomic_t reconcile_worker_got_sighup = false;
+
+/* set once the client has been told the checks are disabled, so the notice
+ * is sent once per session instead of for every statement
+ */
+static bool disabled_notice_sent = false;
+
+/* a hook that already ran is known to still be in the chain. Another library installing its
+ * hook on top of ours is fine as long as it calls ours, so a hook is only reported once a
+ * statement that has to go through it completed without it, eg: COPY (query) without the
+ * executor hook. A reported hook counts as called, it is reported once per backend.
+ */
+static bool utility_hook_called = false;
+static bool executor_hook_called = false;
+static bool oa_hook_called = false;
+/* nesting of the statements run from our utility hook, objects created outside of it
+ * were created by a utility statement that didn't go through it
+ */
+static int utility_hook_depth = 0;
 
 /* Saved hook values in case of unload */
 static ProcessUtility_hook_type prev_ProcessUtility = NULL;
+
+/* checks run by the utility hook, keyed on the statement node type */
+typedef struct UtilityCheck
+{
+    NodeTag tag;
+    const char *name; // statement type shown by aiven_gatekeeper_covered_statements, NULL for the checks of other libraries
+    gatekeeper_check_fn check;
+} UtilityCheck;
+
+static UtilityCheck utility_checks[GATEKEEPER_MAX_CHECKS];
+static int num_utility_checks = 0;
+
+/* denials of the utility statement being checked, reported together once all checks ran */
+typedef struct CollectedDenial
+{
+    const char *statement_type;
+    const char *reason_code;
+    char *message;
+} CollectedDenial;
+
+#define MAX_REPORTED_DENIALS 5
+
+static bool collecting_denials = false;
+static List *collected_denials = NIL;
+
+/* context of the utility statement being checked. Subcommands inherit the context
+ * of the statement they are part of. Outside of the utility hook this is TOPLEVEL.
+ */
+static ProcessUtilityContext current_utility_context = PROCESS_UTILITY_TOPLEVEL;
+#if GATEKEEPER_OBJECT_ACCESS_HOOK
 static object_access_hook_type next_object_access_hook = NULL;
+#endif
+#if GATEKEEPER_EXECUTOR_HOOK
 static ExecutorStart_hook_type prev_ExecutorStart_hook = NULL;
+static ExecutorRun_hook_type prev_ExecutorRun_hook = NULL;
+
+/* the statement timeout is armed by the forced timeout of the running query */
+static bool forced_timeout_armed = false;
 
 /* bug that breaks some extension functionality due to nested queries inadvertently
     reading, but not using, a reserved column name
 */
 static bool BUG_01 = true;
 
+static char *timeout_roles = NULL;
+static int forced_statement_timeout = 0;
+#endif
+
 static bool
 allowed_guc_change_check_hook(bool *newval, void **extra, GucSource source)
 {
@@ -116,653 +523,4887 @@ allowed_guc_change_check_hook(bool *newval, void **extra, GucSource source)
     return !(pg_security_agent_strict || creating_extension || is_security_restricted() || is_elevated());
 }
 
+/* turning runtime strict mode on only ever tightens the checks, allow it in any context.
+ * Turning it off is a configuration change like any other.
+ */
 static bool
-allowed_guc_change_allowed_superusers(char **newval, void **extra, GucSource source)
+strict_runtime_check_hook(bool *newval, void **extra, GucSource source)
+{
+    if (*newval)
+        return true;
+    return allowed_guc_change_check_hook(newval, extra, source);
+}
+
+static bool
+allowed_guc_change_int_check_hook(int *newval, void **extra, GucSource source)
 {
     /* same as with the boolean version */
     return !(pg_security_agent_strict || creating_extension || is_security_restricted() || is_elevated());
 }
 
-/* returns true if the session and current user ids are different */
 static bool
-is_elevated(void)
+allowed_guc_change_allowed_superusers(char **newval, void **extra, GucSource source)
 {
-    /* if current user != session and the current user is
-     * a superuser, but the original session_user is not,
-     * we can say that we are in an elevated context.
-     */
-
-    Oid currentUserId = GetUserId();
-    Oid sessionUserId = GetSessionUserId();
+    List *elemlist;
+    bool valid;
 
-    bool is_superuser;
+    /* same as with the boolean version */
+    if (pg_security_agent_strict || creating_extension || is_security_restricted() || is_elevated())
+        return false;
 
-    /* short circuit if the current and session user are the same
-     * saves on a slightly more expensive role fetch
+    /* the list is re-read on SIGHUP, refuse a value that can't be parsed rather than
+     * silently ending up with no allowed superuser roles
      */
-    if (currentUserId == sessionUserId || CurrentResourceOwner == NULL)
+    valid = split_identifier_list(*newval, &elemlist, NULL);
+    list_free(elemlist);
+
+    if (!valid)
     {
+        GUC_check_errdetail("List syntax is invalid.");
         return false;
     }
-
-    is_superuser = superuser_arg(currentUserId);
-    /* elevated to supersuser when the session auth user does not have superuser privileges */
-    return is_superuser && !CURRENT_ROLE_IS_SUPERUSER;
+    return true;
 }
 
+/* the reserved roles are the only list with ~regex entries, these have to compile */
 static bool
-is_security_restricted(void)
+reserved_roles_check_hook(char **newval, void **extra, GucSource source)
 {
-    /* checks if we are in a security_restricted context
-     * this occurs during VACUUM, ANALYZE, MATERIAL VIEW etc
-     */
-    return InSecurityRestrictedOperation();
+    List *elemlist;
+    char *regex_error;
+
+    if (!allowed_guc_change_allowed_superusers(newval, extra, source))
+        return false;
+
+    split_identifier_list(*newval, &elemlist, NULL);
+    regex_error = invalid_role_regex(elemlist);
+    list_free(elemlist);
+    if (regex_error != NULL)
+    {
+        GUC_check_errdetail("%s", regex_error);
+        return false;
+    }
+    return true;
 }
 
-/* check if a target role is in the list of roles that are permitted to have superuser */
-static bool
-allow_superuser_role(const char *target_role)
+/* duplicated reserved roles are ignored, but likely a typo in the configuration */
+static void
+reserved_roles_assign_hook(const char *newval, void *extra)
 {
-    List *allowed_superuser_list;
-    ListCell *role;
+    List *elemlist;
+    int duplicates;
 
-    if (allowed_superuser_roles)
-    {
-        SplitIdentifierString(pstrdup(allowed_superuser_roles), ',', &allowed_superuser_list);
+    if (split_identifier_list(newval, &elemlist, &duplicates) && duplicates > 0)
+        elog(WARNING, "aiven.pg_security_agent_reserved_roles contains %d duplicate entries", duplicates);
+    list_free(elemlist);
 
-        foreach (role, allowed_superuser_list)
-        {
-            char *allowed_role = (char *)lfirst(role);
-            if (strcmp(target_role, allowed_role) == 0)
-            {
-                list_free(allowed_superuser_list);
-                return true;
-            }
-        }
-        list_free(allowed_superuser_list);
-    }
-    return false;
+    reserved_status_roleid = InvalidOid;
+    role_regex_cache_reset();
 }
 
+/* read the reserved roles from aiven.pg_security_config_file, entries are separated by
+ * commas or newlines. A file that is missing or can't be parsed is ignored with a warning,
+ * NULL then, leaving only the setting.
+ */
 static char *
-allow_role_stmt(void)
+read_reserved_roles_file(const char *path)
 {
-    if (pg_security_agent_strict)
-        return "ROLE modification to SUPERUSER/privileged role not allowed";
+    FILE *file;
+    StringInfoData buf;
+    char chunk[1024];
+    size_t nread;
+    List *elemlist;
+    bool valid;
+    char *regex_error;
+    int i;
 
-    if (creating_extension)
-        return "ROLE modification to SUPERUSER/privileged role not allowed in extensions";
+    file = AllocateFile(path, "r");
+    if (file == NULL)
+    {
+        ereport(WARNING,
+                (errcode_for_file_access(),
+                 errmsg("could not open aiven.pg_security_config_file \"%s\": %m", path)));
+        return NULL;
+    }
 
-    if (is_security_restricted())
-        return "ROLE modification to SUPERUSER/privileged role not allowed in SECURITY_RESTRICTED_OPERATION";
+    initStringInfo(&buf);
+    while ((nread = fread(chunk, 1, sizeof(chunk), file)) > 0)
+        appendBinaryStringInfo(&buf, chunk, nread);
+    FreeFile(file);
 
-    if (is_elevated())
-        return "ROLE modification to SUPERUSER/privileged role not allowed";
+    for (i = 0; i < buf.len; i++)
+    {
+        if (buf.data[i] == '\n' || buf.data[i] == '\r')
+            buf.data[i] = ',';
+    }
 
-    return NULL;
-}
+    valid = split_identifier_list(buf.data, &elemlist, NULL);
+    if (!valid)
+    {
+        list_free(elemlist);
+        ereport(WARNING,
+                (errcode(ERRCODE_CONFIG_FILE_ERROR),
+                 errmsg("aiven.pg_security_config_file \"%s\" has an invalid list syntax, ignoring it", path)));
+        return NULL;
+    }
 
-static void
-allow_granted_roles(List *addroleto)
-{
-    ListCell *role_cell;
-    RoleSpec *rolemember;
-    Oid role_member_oid;
-    char *result;
-    // check if any of the roles we are trying to add to have superuser
-    foreach (role_cell, addroleto)
+    regex_error = invalid_role_regex(elemlist);
+    list_free(elemlist);
+    if (regex_error != NULL)
     {
-        rolemember = lfirst(role_cell);
-        role_member_oid = get_rolespec_oid(rolemember, false);
-        result = allow_grant_or_alter_role(role_member_oid);
-        if (result != NULL)
-        {
-            elog(ERROR, "%s", result);
-            return;
-        }
+        ereport(WARNING,
+                (errcode(ERRCODE_CONFIG_FILE_ERROR),
+                 errmsg("aiven.pg_security_config_file \"%s\" is invalid, ignoring it", path),
+                 errdetail("%s", regex_error)));
+        return NULL;
     }
+    return buf.data;
 }
 
-static char *
-allow_grant_or_alter_role(Oid role_oid)
+/* the file is read on every configuration reload, its entries are kept as the extra */
+static bool
+reserved_roles_file_check_hook(char **newval, void **extra, GucSource source)
 {
-    /* check if we are trying to alter a reserved (privileged) role, or grant
-     * access to superuser or privileged roles
-     * we first need to fetch the oid's of the reserved roles.
-     * these would be nice to pull from header files, but the required
-     * headers are generated using src/backend/catalog/genbki.pl and aren't guaranteed to exist.
-     */
-    Oid role_pg_execute_server_program;
-    Oid role_pg_read_server_files;
-    Oid role_pg_write_server_files;
+    char *entries;
 
-    role_pg_execute_server_program = get_role_oid("pg_execute_server_program", true);
-    role_pg_read_server_files = get_role_oid("pg_read_server_files", true);
-    role_pg_write_server_files = get_role_oid("pg_write_server_files", true);
+    /* same as with the boolean version */
+    if (pg_security_agent_strict || creating_extension || is_security_restricted() || is_elevated())
+        return false;
+
+    *extra = NULL;
+    if (*newval == NULL || (*newval)[0] == '\0')
+        return true;
 
-    if (superuser_arg(role_oid) ||
-        is_member_of_role(role_oid, role_pg_execute_server_program) ||
-        is_member_of_role(role_oid, role_pg_read_server_files) ||
-        is_member_of_role(role_oid, role_pg_write_server_files))
+    entries = read_reserved_roles_file(*newval);
+    if (entries == NULL)
+        return true;
+
+    /* the GUC machinery frees the extra, from PG16 on it has to be in the GUC memory context */
+#if PG16_GTE
+    *extra = guc_strdup(LOG, entries);
+#else
+    *extra = strdup(entries);
+#endif
+    if (*extra == NULL)
     {
-        return allow_role_stmt();
+        GUC_check_errdetail("Out of memory.");
+        return false;
     }
-    return NULL;
+    return true;
 }
 
 static void
-gatekeeper_checks(PROCESS_UTILITY_PARAMS)
+reserved_roles_file_assign_hook(const char *newval, void *extra)
 {
-    Node *stmt;
-    CopyStmt *copyStmt;
-    CreateRoleStmt *createRoleStmt;
-    AlterRoleStmt *alterRoleStmt;
-    GrantRoleStmt *grantRoleStmt;
-    CreateFunctionStmt *createFuncStmt;
-    CreateExtensionStmt *createExtStmt;
-    ListCell *option;
-    DefElem *defel;
-    List *addroleto;
-    ListCell *grantRoleCell;
-    AccessPriv *priv;
-    Oid roleoid;
-    char *funcLang;
-    int i;
-    bool checkBody;
-    char *sqlBody = "";
-    char *result;
+    reserved_roles_from_file = (const char *)extra;
+    reserved_status_roleid = InvalidOid;
+    role_regex_cache_reset();
+}
 
-    /* if the agent is disabled, skip all checks */
-    if (!pg_security_agent_enabled)
-    {
-        /* execute the actual query */
-        if (prev_ProcessUtility)
-            prev_ProcessUtility(PROCESS_UTILITY_ARGS);
-        else
-            standard_ProcessUtility(PROCESS_UTILITY_ARGS);
+static bool
+allowed_guc_change_role_name_pattern(char **newval, void **extra, GucSource source)
+{
+    regex_t regex;
+    char errstr[100];
+    int rc;
 
-        /* we are done executing, exit the function */
-        return;
+    /* same as with the boolean version */
+    if (pg_security_agent_strict || creating_extension || is_security_restricted() || is_elevated())
+        return false;
+
+    if (*newval == NULL || (*newval)[0] == '\0')
+        return true;
+
+    rc = compile_role_regex(*newval, &regex);
+    if (rc != REG_OKAY)
+    {
+        pg_regerror(rc, &regex, errstr, sizeof(errstr));
+        GUC_check_errdetail("Invalid regular expression: %s.", errstr);
+        return false;
     }
+    pg_regfree(&regex);
+    return true;
+}
 
-    /* get the utilty statment from the planner
-     * https://github.com/postgres/postgres/blob/24d2b2680a8d0e01b30ce8a41c4eb3b47aca5031/src/backend/tcop/utility.c#L575
-     */
-    stmt = pstmt->utilityStmt;
-    /* switch between the types to see if we care about this stmt */
-    switch (stmt->type)
+static bool
+allowed_guc_change_reserved_functions(char **newval, void **extra, GucSource source)
+{
+    List *elemlist;
+    bool valid;
+
+    /* same as with the boolean version */
+    if (pg_security_agent_strict || creating_extension || is_security_restricted() || is_elevated())
+        return false;
+
+    valid = split_identifier_list(*newval, &elemlist, NULL);
+    list_free(elemlist);
+    if (!valid)
     {
-    case T_AlterRoleStmt: // ALTER ROLE
-        alterRoleStmt = (AlterRoleStmt *)stmt;
+        GUC_check_errdetail("List syntax is invalid.");
+        return false;
+    }
+    return true;
+}
 
-        // check we aren't altering a reserved role (existing superuser)
-        roleoid = get_rolespec_oid(alterRoleStmt->role, true);
-        result = allow_grant_or_alter_role(roleoid);
-        if (result != NULL)
-            elog(ERROR, "%s", result);
+/* the trace id is set by the application and written into every audit record, only accept
+ * the characters of the usual trace context formats, eg: a W3C traceparent header
+ */
+static bool
+trace_id_check_hook(char **newval, void **extra, GucSource source)
+{
+    const char *c;
 
-        // check if we are altering with superuser
-        foreach (option, alterRoleStmt->options)
+    if (*newval == NULL)
+        return true;
+    if (strlen(*newval) > MAX_TRACE_ID_LENGTH)
+    {
+        GUC_check_errdetail("The trace id can be at most %d characters long.", MAX_TRACE_ID_LENGTH);
+        return false;
+    }
+    for (c = *newval; *c != '\0'; c++)
+    {
+        if (!isalnum((unsigned char)*c) && strchr("-_.:", *c) == NULL)
         {
-            defel = (DefElem *)lfirst(option);
-            // superuser or nosuperuser is supplied (both are treated as defname superuser) and check that the arg is set to true
-            if (strncmp(defel->defname, "superuser", 10) == 0 && defGetBoolean(defel))
-            {
-                // regardless of context (elevated privilege or not), check if the target role is allowed to be superuser
-                if (!allow_superuser_role(alterRoleStmt->role->rolename))
-                    elog(ERROR, "Role %s not in permitted superuser list", alterRoleStmt->role->rolename);
-
-                result = allow_role_stmt();
-                if (result != NULL)
-                    elog(ERROR, "%s", result);
-            }
+            GUC_check_errdetail("The trace id can only contain letters, digits and \"-_.:\".");
+            return false;
         }
-        break;
-    case T_CreateRoleStmt: // CREATE ROLE
-        createRoleStmt = (CreateRoleStmt *)stmt;
+    }
+    return true;
+}
 
-        foreach (option, createRoleStmt->options)
-        {
-            defel = (DefElem *)lfirst(option);
+static bool
+allowed_guc_change_copy_paths(char **newval, void **extra, GucSource source)
+{
+    char *rawstring;
+    List *elemlist;
+    bool valid;
 
-            // check if we are granting superuser
-            if (strncmp(defel->defname, "superuser", 10) == 0 && defGetBoolean(defel))
-            {
-                // regardless of context (elevated privilege or not), check if the target role is allowed to be superuser
-                if (!allow_superuser_role(createRoleStmt->role))
-                    elog(ERROR, "Role %s not in permitted superuser list", createRoleStmt->role);
+    /* same as with the boolean version */
+    if (pg_security_agent_strict || creating_extension || is_security_restricted() || is_elevated())
+        return false;
 
-                result = allow_role_stmt();
-                if (result != NULL)
-                    elog(ERROR, "%s", result);
-            }
+    rawstring = pstrdup(*newval);
+    valid = SplitDirectoriesString(rawstring, ',', &elemlist);
+    list_free_deep(elemlist);
+    pfree(rawstring);
 
-            // check if user is being added to a role that has superuser or other high privilege
-            if (strncmp(defel->defname, "addroleto", 10) == 0)
-            {
-                addroleto = (List *)defel->arg;
-                allow_granted_roles(addroleto);
-            }
-        }
-        break;
-    case T_DropRoleStmt: // DROP ROLE
-        // don't allow dropping role from elevated context
-        // this should be a check for dropping reserved roles
-        // allow_role_stmt();
-        break;
-    case T_GrantRoleStmt: // GRANT ROLE
-        grantRoleStmt = (GrantRoleStmt *)stmt;
+    if (!valid)
+    {
+        GUC_check_errdetail("List syntax is invalid.");
+        return false;
+    }
+    return true;
+}
 
-        // check if any of the granted roles have superuser permission
-        foreach (grantRoleCell, grantRoleStmt->granted_roles)
-        {
-            priv = (AccessPriv *)lfirst(grantRoleCell);
-            roleoid = get_role_oid(priv->priv_name, false);
-            result = allow_grant_or_alter_role(roleoid);
-            if (result != NULL)
-                elog(ERROR, "%s", result);
-        }
-        break;
-    case T_CopyStmt: // COPY
+static bool
+allowed_guc_change_sensitive_columns(char **newval, void **extra, GucSource source)
+{
+    List *elemlist;
+    ListCell *cell;
+    char *schema;
+    char *table;
+    char *column;
 
-        /* get the actual copy statement so we can check is_program and filename */
-        copyStmt = (CopyStmt *)stmt;
+    /* same as with the boolean version */
+    if (pg_security_agent_strict || creating_extension || is_security_restricted() || is_elevated())
+        return false;
 
-        /* check if TO/FROM PROGRAM
-         * we deny this regardless of the context we are running in
-         */
-        if (copyStmt->is_program)
-        {
-            elog(ERROR, "COPY TO/FROM PROGRAM not allowed");
-            return;
-        }
-        /* otherwise, we don't want copy TO/FROM FILE
-         * in an elevated context
-         */
-        if (copyStmt->filename)
+    if (!split_identifier_list(*newval, &elemlist, NULL))
+    {
+        GUC_check_errdetail("List syntax is invalid.");
+        return false;
+    }
+    foreach (cell, elemlist)
+    {
+        if (!gatekeeper_parse_sensitive_column((char *)lfirst(cell), &schema, &table, &column))
         {
-            if (pg_security_agent_strict)
-            {
-                elog(ERROR, "COPY TO/FROM FILE not allowed");
-                return;
-            }
-            if (creating_extension)
-            {
-                elog(ERROR, "COPY TO/FROM FILE not allowed in extensions");
-                return;
-            }
-            if (is_security_restricted())
-            {
-                elog(ERROR, "COPY TO/FROM FILE not allowed in SECURITY_RESTRICTED_OPERATION");
-                return;
-            }
-            if (is_elevated())
-            {
-                elog(ERROR, "COPY TO/FROM FILE not allowed");
-                return;
-            }
+            GUC_check_errdetail("Entry \"%s\" is not of the form schema.table.column.", (char *)lfirst(cell));
+            list_free(elemlist);
+            return false;
         }
-        break;
-    case T_VariableSetStmt:
-        /* SET SESSION_AUTHORIZATION would allow bypassing of our dumb privilege escalation check.
-         * even though this should be blocked in extension installation, due to
-         *  ERROR:  cannot set parameter "session_authorization" within security-definer function
-         * so don't do anything.
-         */
-        break;
-    case T_CreateFunctionStmt:
-        createFuncStmt = (CreateFunctionStmt *)stmt;
-        checkBody = false; // used for versions prior to 14, where the sql_body is not availble in the CreateFuncStmt struct
+    }
+    list_free(elemlist);
+    return true;
+}
 
-        foreach (option, createFuncStmt->options)
-        {
-            defel = (DefElem *)lfirst(option);
+/* the channel name has the same limits as NOTIFY */
+static bool
+allowed_guc_change_notify_channel(char **newval, void **extra, GucSource source)
+{
+    /* same as with the boolean version */
+    if (pg_security_agent_strict || creating_extension || is_security_restricted() || is_elevated())
+        return false;
 
-            /* check if of language type internal
-             * this is not accessible to untrusted users, so disable if elevated context
-             */
-            if (strncmp(defel->defname, "language", 9) == 0)
-            {
-                funcLang = defGetString(defel);
-                /* check if restricted language type */
-                if (strncmp(funcLang, "plperlu", 8) == 0 ||
-                    strncmp(funcLang, "plpythonu", 10) == 0)
-                {
-                    if (pg_security_agent_strict)
-                    {
-                        elog(ERROR, "LANGUAGE %s not allowed", funcLang);
-                        return;
-                    }
-                    if (creating_extension)
-                    {
-                        elog(ERROR, "LANGUAGE %s not allowed in extensions", funcLang);
-                        return;
-                    }
-                    if (is_security_restricted())
-                    {
-                        elog(ERROR, "LANGUAGE %s not allowed in SECURITY_RESTRICTED_OPERATION", funcLang);
-                        return;
-                    }
-                    if (is_elevated())
-                    {
-                        elog(ERROR, "LANGUAGE %s not allowed", funcLang);
-                        return;
-                    }
-                }
-                else if (strncmp(funcLang, "internal", 9) == 0 && (pg_security_agent_strict || creating_extension || is_elevated() || is_security_restricted()))
-                {
-                    checkBody = true;
-                }
-            }
-            /* extract the sql body so we can use it to check if restricted internal
-             * function is being declared
-             */
-            if (strncmp(defel->defname, "as", 3) == 0)
-            {
-                sqlBody = defGetString(defel);
-            }
-        }
-        /* we need to check the sql body, as we are in restricted context and the function is of type internal*/
-        if (checkBody == true)
-        {
-            for (i = 0; i < NUM_RESERVED_FUNCS; i++)
-            {
-                /* internal names are case sensitive, so strcmp is fine here */
-                if (strncmp(reserved_func_names[i], sqlBody, 28) == 0)
-                {
-                    elog(ERROR, "using builtin function %s is not allowed", sqlBody);
-                    return;
-                }
-            }
-        }
-        break;
-    case T_CreateExtensionStmt:
-        /* block file_fdw extension. Case sensitive compare is ok, since the extension name is lower case when read from extname*/
-        createExtStmt = (CreateExtensionStmt *)stmt;
-        if (strncmp(createExtStmt->extname, "file_fdw", 9) == 0)
-        {
-            elog(ERROR, "file_fdw extension not allowed");
-            return;
-        }
-        break;
-    default:
-        break;
+    if (*newval == NULL || (*newval)[0] == '\0' || strlen(*newval) >= NAMEDATALEN)
+    {
+        GUC_check_errdetail("Channel name must be between 1 and %d bytes.", NAMEDATALEN - 1);
+        return false;
     }
+    return true;
+}
 
-    /* execute the actual query */
-    if (prev_ProcessUtility)
-        prev_ProcessUtility(PROCESS_UTILITY_ARGS);
-    else
-        standard_ProcessUtility(PROCESS_UTILITY_ARGS);
+/* the notify worker connects to this database at startup */
+static bool
+allowed_guc_change_notify_database(char **newval, void **extra, GucSource source)
+{
+    /* same as with the boolean version */
+    if (pg_security_agent_strict || creating_extension || is_security_restricted() || is_elevated())
+        return false;
+
+    if (*newval == NULL || (*newval)[0] == '\0' || strlen(*newval) >= NAMEDATALEN)
+    {
+        GUC_check_errdetail("Database name must be between 1 and %d bytes.", NAMEDATALEN - 1);
+        return false;
+    }
+    return true;
 }
 
-/* straight copy from fmgr.c
- * this function isn't exported by fmgr.c, so just
- * recreate it here
+/* the metrics worker replaces the file with durable_rename, inside the data directory that
+ * could overwrite pg_hba.conf or postgresql.auto.conf. Only absolute paths outside of it,
+ * also after resolving symbolic links.
  */
-static const FmgrBuiltin *
-fmgr_lookupByName(const char *name)
+static bool
+allowed_guc_change_metrics_file(char **newval, void **extra, GucSource source)
 {
-    int i;
+    char *path;
+    char *resolved;
+    char *datadir;
 
-    for (i = 0; i < fmgr_nbuiltins; i++)
+    /* same as with the boolean version */
+    if (pg_security_agent_strict || creating_extension || is_security_restricted() || is_elevated())
+        return false;
+
+    if (*newval == NULL || (*newval)[0] == '\0')
+        return true;
+    if (!is_absolute_path(*newval))
     {
-        // switched to strncmp, 28 is the current max size that can be expected for name, as from reserved_func_names
-        if (strncmp(name, fmgr_builtins[i].funcName, 28) == 0)
-            return fmgr_builtins + i;
+        GUC_check_errdetail("The metrics file must be an absolute path.");
+        return false;
     }
-    return NULL;
+    if (DataDir == NULL)
+        return true;
+
+    path = pstrdup(*newval);
+    canonicalize_path(path);
+    resolved = resolve_copy_path(path);
+    datadir = resolve_copy_path(DataDir);
+    if (path_is_prefix_of_path(DataDir, path) ||
+        (resolved != NULL && path_is_prefix_of_path(datadir != NULL ? datadir : DataDir, resolved)))
+    {
+        GUC_check_errdetail("The metrics file must be outside of the data directory.");
+        return false;
+    }
+    return true;
 }
 
+/* returns true if the session and current user ids are different */
 static bool
-set_reserved_oids()
+is_elevated(void)
 {
-    /* sets the min and max_reserved_oid variables, allowing for skipping builtins search
-     * if an oid is clearly not going to be in the builtins.
+    /* if current user != session and the current user is
+     * a superuser, but the original session_user is not,
+     * we can say that we are in an elevated context.
      */
-    const FmgrBuiltin *builtin;
-    int i;
 
-    reserved_func_oids = (Oid *)malloc(NUM_RESERVED_FUNCS * sizeof(Oid));
-    if (reserved_func_oids == NULL)
+    Oid currentUserId = GetUserId();
+    Oid sessionUserId = GetSessionUserId();
+
+    bool is_superuser;
+
+    /* short circuit if the current and session user are the same
+     * saves on a slightly more expensive role fetch
+     */
+    if (currentUserId == sessionUserId || CurrentResourceOwner == NULL)
     {
         return false;
     }
-    /* loop through the function names we have defined as reserved
-     * lookup the oid of the function so that we can use this for future
-     * evaluations rather than comparing strings
-     * and find the maximum oid
+
+    is_superuser = superuser_arg(currentUserId);
+    /* elevated to supersuser when the session auth user does not have superuser privileges */
+    return is_superuser && !CURRENT_ROLE_IS_SUPERUSER;
+}
+
+static bool
+is_security_restricted(void)
+{
+    /* checks if we are in a security_restricted context
+     * this occurs during VACUUM, ANALYZE, MATERIAL VIEW etc
      */
-    for (i = 0; i < NUM_RESERVED_FUNCS; i++)
+    return InSecurityRestrictedOperation();
+}
+
+/* strict mode as the checks apply it, either setting enables it and a break glass
+ * session is exempt. Configuration changes and the reserved roles fallback only use
+ * the startup setting.
+ */
+static bool
+is_strict_mode_enabled(void)
+{
+    return (pg_security_agent_strict || pg_security_agent_strict_runtime) && !break_glass_active;
+}
+
+/* returns the reason code for the context we are running in, or NULL when
+ * not in strict mode or any elevated context. The order matches the checks
+ * in allow_role_stmt so the reason lines up with the message.
+ */
+static const char *
+restricted_context_reason(void)
+{
+    if (is_strict_mode_enabled())
+        return "strict_mode";
+    if (creating_extension)
+        return "creating_extension";
+    if (is_security_restricted())
+        return "security_restricted";
+    if (is_elevated())
+        return "elevated";
+    return NULL;
+}
+
+/* builds the single line json audit record, message can be NULL */
+static char *
+build_audit_json(const char *statement_type, const char *reason_code, const char *action, const char *message)
+{
+    StringInfoData buf;
+    char *role_name;
+    char *database_name = NULL;
+    const char *application_name;
+
+    role_name = GetUserNameFromId(GetUserId(), true);
+    /* helps attributing the statement to a client service, often left empty */
+    application_name = GetConfigOption("application_name", true, false);
+    if (application_name != NULL && application_name[0] == '\0')
+        application_name = NULL;
+    /* the database name lookup needs catalog access */
+    if (IsTransactionState())
+        database_name = get_database_name(MyDatabaseId);
+
+    initStringInfo(&buf);
+    appendStringInfoString(&buf, "{\"timestamp\":");
+    escape_json(&buf, timestamptz_to_str(GetCurrentTimestamp()));
+    appendStringInfoString(&buf, ",\"role\":");
+    if (role_name)
+        escape_json(&buf, role_name);
+    else
+        appendStringInfoString(&buf, "null");
+    appendStringInfoString(&buf, ",\"database\":");
+    if (database_name)
+        escape_json(&buf, database_name);
+    else
+        appendStringInfoString(&buf, "null");
+    appendStringInfoString(&buf, ",\"application_name\":");
+    if (application_name)
+        escape_json(&buf, application_name);
+    else
+        appendStringInfoString(&buf, "null");
+    appendStringInfoString(&buf, ",\"statement_type\":");
+    escape_json(&buf, statement_type);
+    appendStringInfoString(&buf, ",\"reason_code\":");
+    escape_json(&buf, reason_code ? reason_code : "unknown");
+    appendStringInfoString(&buf, ",\"action\":");
+    escape_json(&buf, action);
+    appendStringInfoString(&buf, ",\"message\":");
+    if (message)
+        escape_json(&buf, message);
+    else
+        appendStringInfoString(&buf, "null");
+    appendStringInfoString(&buf, ",\"change_reason\":");
+    if (change_reason != NULL && change_reason[0] != '\0')
+        escape_json(&buf, change_reason);
+    else
+        appendStringInfoString(&buf, "null");
+    appendStringInfoString(&buf, ",\"trace_id\":");
+    if (trace_id != NULL && trace_id[0] != '\0')
+        escape_json(&buf, trace_id);
+    else
+        appendStringInfoString(&buf, "null");
+    appendStringInfoChar(&buf, '}');
+
+    return buf.data;
+}
+
+/* emits an audit record, for elevel ERROR this aborts the statement
+ * text: the message itself with the audit fields in the server log detail
+ * json: a single line json object in the server log, followed by the message for the client
+ */
+static void
+gatekeeper_audit_log(int elevel, const char *statement_type, const char *reason_code, const char *action, const char *message)
+{
+    char *json;
+    char *role_name;
+    char *database_name = NULL;
+    const char *application_name;
+
+    if (pg_security_audit_format == AUDIT_FORMAT_JSON)
     {
-        if ((builtin = fmgr_lookupByName(reserved_func_names[i])) != NULL)
-        {
-            reserved_func_oids[i] = builtin->foid;
-            if (builtin->foid < min_reserved_oid)
-            {
-                min_reserved_oid = builtin->foid;
-            }
-            else if (builtin->foid > max_reserved_oid)
-            {
-                max_reserved_oid = builtin->foid;
-            }
-        }
+        json = build_audit_json(statement_type, reason_code, action, message);
+        ereport(LOG_SERVER_ONLY,
+                (errmsg("%s", json),
+                 errhidestmt(true),
+                 errhidecontext(true)));
+        pfree(json);
+
+        /* the json record already went to the log, only the client needs the message */
+        if (elevel >= WARNING)
+            ereport(elevel,
+                    (errcode(ERRCODE_INSUFFICIENT_PRIVILEGE),
+                     errmsg("%s", message)));
+        return;
     }
-    return true;
+
+    role_name = GetUserNameFromId(GetUserId(), true);
+    /* helps attributing the statement to a client service, often left empty */
+    application_name = GetConfigOption("application_name", true, false);
+    if (application_name != NULL && application_name[0] == '\0')
+        application_name = NULL;
+    /* the database name lookup needs catalog access */
+    if (IsTransactionState())
+        database_name = get_database_name(MyDatabaseId);
+
+    ereport(elevel,
+            (errcode(ERRCODE_INSUFFICIENT_PRIVILEGE),
+             errmsg("%s", message),
+             errdetail_log("statement_type=%s reason_code=%s action=%s role=%s database=%s application_name=%s change_reason=%s trace_id=%s",
+                           statement_type,
+                           reason_code ? reason_code : "unknown",
+                           action,
+                           role_name ? role_name : "[unknown]",
+                           database_name ? database_name : "[unknown]",
+                           application_name ? application_name : "[unknown]",
+                           change_reason != NULL && change_reason[0] != '\0' ? change_reason : "[unknown]",
+                           trace_id != NULL && trace_id[0] != '\0' ? trace_id : "[unknown]")));
 }
 
-/* hook to check if the function being called is not in the disallowed-list
- * obviously allow list of built-in functions would be prefered, but this list of disallowed is tiny
- * and we want to ensure minimum impact on performance and function.
+/* privileged statements, eg: superuser grants and extension installs, that passed the other checks.
+ * With aiven.pg_security_require_change_reason on they need a justification in
+ * aiven.pg_security_change_reason, with that or aiven.pg_security_audit_allowed on
+ * the allowed statement gets an audit record.
  */
 static void
-gatekeeper_oa_hook(ObjectAccessType access,
-                   Oid classId,
-                   Oid objectId,
-                   int subId,
-                   void *arg)
+privileged_statement_checks(const char *statement_type, const char *description)
 {
-    const FmgrBuiltin *builtin;
-    int i;
+    if (require_change_reason && (change_reason == NULL || change_reason[0] == '\0'))
+    {
+        gatekeeper_deny(statement_type, "change_reason_missing", "%s requires a change reason, SET aiven.pg_security_change_reason first", description);
+        return;
+    }
 
-    /* only check function if security agent is enabled */
-    if (pg_security_agent_enabled)
+    if ((require_change_reason || audit_allowed) && !denials_pending())
+        gatekeeper_audit_log(LOG, statement_type, "privileged_statement", "allow", description);
+}
+
+/* deny the current statement with an audit record
+ * in a monitor only build this returns, callers must not rely on it aborting.
+ * While the utility checks run the denial is only collected, see raise_collected_denials.
+ */
+static void
+gatekeeper_deny(const char *statement_type, const char *reason_code, const char *fmt, ...)
+{
+    StringInfoData message;
+    va_list args;
+    int needed;
+
+    initStringInfo(&message);
+    for (;;)
     {
-        switch (access) // we are only interested in the OAT_FUNCTION_EXECUTE ObjectAccessType
-        {
-        case OAT_FUNCTION_EXECUTE:
-            /* check if the objecid is within range of our reserved oids
-             * this allows faster evalation, rather than having to loop through
-             * arrays for each function call.
-             */
-            if (objectId >= min_reserved_oid && objectId <= max_reserved_oid)
-            {
-                for (i = 0; i < NUM_RESERVED_FUNCS; i++)
-                {
-                    /* lookup the oid to see if it is in our reserved list
-                     */
-                    if (reserved_func_oids[i] == objectId)
-                    {
-                        /* check if we are in a privileged context and disallow the function executions */
-                        if (pg_security_agent_strict || creating_extension || is_elevated() || is_security_restricted())
-                        {
-                            /* get the function information so that error message can be more friendly */
-                            if ((builtin = fmgr_lookupByName(reserved_func_names[i])) != NULL)
-                            {
-                                elog(ERROR, "using builtin function %s is not allowed", builtin->funcName);
-                                return;
-                            }
-                        }
-                        /* extra check, this is to enforce only superuser can call this function in normal
-                         * context. Otherwise PG uses the grant system, which could lead to roles being
-                         * granted execute privilege on the funcion and still being able to call it.
-                         * This is not too serious, since non-superusers can't read outside reserved paths (for example)
-                         * but rather be strict.
-                         */
-                        if (!superuser())
-                        {
-                            if ((builtin = fmgr_lookupByName(reserved_func_names[i])) != NULL)
-                            {
-                                elog(ERROR, "using builtin function %s is not allowed by non-superusers", builtin->funcName);
-                                return;
-                            }
-                        }
-                        break;
-                    }
-                }
-            }
+        va_start(args, fmt);
+        needed = appendStringInfoVA(&message, fmt, args);
+        va_end(args);
+        if (needed == 0)
             break;
-        default:
+        enlargeStringInfo(&message, needed);
+    }
+
+    if (collecting_denials)
+    {
+        CollectedDenial *denial = (CollectedDenial *)palloc(sizeof(CollectedDenial));
+
+        denial->statement_type = statement_type;
+        denial->reason_code = reason_code;
+        denial->message = message.data;
+        collected_denials = lappend(collected_denials, denial);
+        return;
+    }
+
+    record_denial(statement_type);
+    count_denial(statement_type);
+    queue_denial_notification(statement_type, reason_code, DENY_ACTION, message.data);
+    gatekeeper_audit_log(DENY_ELEVEL, statement_type, reason_code, DENY_ACTION, message.data);
+}
+
+/* deny in strict mode and for the callers that aren't reserved roles, the rule of the
+ * statements only the reserved roles can run. Returns true when the statement was denied.
+ */
+static bool
+deny_unless_reserved(const char *statement_type, const char *fmt, ...)
+{
+    StringInfoData message;
+    va_list args;
+    int needed;
+    const char *reason_code;
+
+    if (is_strict_mode_enabled())
+        reason_code = "strict_mode";
+    else if (!is_reserved_caller())
+        reason_code = "caller_not_reserved";
+    else
+        return false;
+
+    initStringInfo(&message);
+    for (;;)
+    {
+        va_start(args, fmt);
+        needed = appendStringInfoVA(&message, fmt, args);
+        va_end(args);
+        if (needed == 0)
             break;
-        }
+        enlargeStringInfo(&message, needed);
     }
 
-    if (next_object_access_hook)
-        (*next_object_access_hook)(access, classId, objectId, subId, arg);
+    gatekeeper_deny(statement_type, reason_code, "%s", message.data);
+    return true;
+}
+
+/* deny the current statement with an audit record, also in a monitor only build */
+static void
+gatekeeper_hard_deny(const char *statement_type, const char *reason_code, const char *message)
+{
+    record_denial(statement_type);
+    count_denial(statement_type);
+    queue_denial_notification(statement_type, reason_code, "deny", message);
+    gatekeeper_audit_log(ERROR, statement_type, reason_code, "deny", message);
+}
+
+/* a denial of the current utility statement was collected and is going to abort it */
+static bool
+denials_pending(void)
+{
+    return collecting_denials && collected_denials != NIL;
+}
+
+#if !GATEKEEPER_MONITOR_ONLY
+/* abort the statement with all denials the utility checks collected, so they can be fixed at once,
+ * eg: CREATE ROLE x SUPERUSER IN ROLE pg_execute_server_program. Each denial gets its own audit
+ * record and notification, the statement is counted once. The error message is the first denial,
+ * followed by up to MAX_REPORTED_DENIALS of the others.
+ */
+static void
+raise_collected_denials(void)
+{
+    List *denials = collected_denials;
+    CollectedDenial *first;
+    CollectedDenial *denial;
+    StringInfoData message;
+    ListCell *cell;
+    int reported = 0;
+
+    collected_denials = NIL;
+    if (denials == NIL)
+        return;
+
+    first = (CollectedDenial *)linitial(denials);
+    record_denial(first->statement_type);
+    count_denial(first->statement_type);
+
+    initStringInfo(&message);
+    appendStringInfoString(&message, first->message);
+    if (list_length(denials) > 1)
+        appendStringInfo(&message, "; %d more violations:", list_length(denials) - 1);
+
+    foreach (cell, denials)
+    {
+        denial = (CollectedDenial *)lfirst(cell);
+        queue_denial_notification(denial->statement_type, denial->reason_code, "deny", denial->message);
+        if (denial == first)
+            continue;
+
+        gatekeeper_audit_log(LOG, denial->statement_type, denial->reason_code, "deny", denial->message);
+        if (reported < MAX_REPORTED_DENIALS)
+            appendStringInfo(&message, "%s %s", reported > 0 ? ";" : "", denial->message);
+        else if (reported == MAX_REPORTED_DENIALS)
+            appendStringInfoString(&message, "; ...");
+        reported++;
+    }
+
+    gatekeeper_audit_log(ERROR, first->statement_type, first->reason_code, "deny", message.data);
+}
+#endif
+
+/* put the denial in the shared queue for the notify worker */
+static void
+queue_denial_notification(const char *statement_type, const char *reason_code, const char *action, const char *message)
+{
+    char *payload;
+    Latch *worker_latch;
+
+    if (!denial_notify_enabled || gatekeeper_state == NULL)
+        return;
+
+    payload = build_audit_json(statement_type, reason_code, action, message);
+    /* long messages don't fit in the queue entry, send the record without the message */
+    if (strlen(payload) >= DENIAL_PAYLOAD_LEN)
+        payload = build_audit_json(statement_type, reason_code, action, NULL);
+    if (strlen(payload) >= DENIAL_PAYLOAD_LEN)
+        return;
+
+    LWLockAcquire(gatekeeper_state->lock, LW_EXCLUSIVE);
+    if (gatekeeper_state->queue_head - gatekeeper_state->queue_tail >= DENIAL_QUEUE_SIZE)
+    {
+        gatekeeper_state->queue_tail++;
+        gatekeeper_state->queue_dropped++;
+    }
+    strlcpy(gatekeeper_state->queue[gatekeeper_state->queue_head % DENIAL_QUEUE_SIZE], payload, DENIAL_PAYLOAD_LEN);
+    gatekeeper_state->queue_head++;
+    worker_latch = gatekeeper_state->worker_latch;
+    LWLockRelease(gatekeeper_state->lock);
+
+    if (worker_latch != NULL)
+        SetLatch(worker_latch);
+}
+
+/* count the denial against the current role and write an anomaly record once the
+ * role reaches aiven.pg_security_anomaly_threshold denials within
+ * aiven.pg_security_anomaly_window seconds. The window restarts after each anomaly.
+ */
+static void
+count_denial(const char *statement_type)
+{
+    int i;
+
+    if (gatekeeper_state == NULL)
+        return;
+
+    LWLockAcquire(gatekeeper_state->lock, LW_EXCLUSIVE);
+    gatekeeper_state->denials_total++;
+    for (i = 0; i < gatekeeper_state->num_metrics; i++)
+    {
+        if (strcmp(gatekeeper_state->metrics[i].statement_type, statement_type) == 0)
+            break;
+    }
+    if (i == gatekeeper_state->num_metrics && i < MAX_STATEMENT_METRICS)
+    {
+        strlcpy(gatekeeper_state->metrics[i].statement_type, statement_type, NAMEDATALEN);
+        gatekeeper_state->num_metrics++;
+    }
+    if (i < gatekeeper_state->num_metrics)
+        gatekeeper_state->metrics[i].denials++;
+    LWLockRelease(gatekeeper_state->lock);
+}
+
+static void
+record_denial(const char *statement_type)
+{
+    DenialCounter *counters;
+    DenialCounter *counter = NULL;
+    TimestampTz now;
+    Oid roleid;
+    int count;
+    int i;
+
+    if (anomaly_threshold <= 0 || gatekeeper_state == NULL)
+        return;
+
+    now = GetCurrentTimestamp();
+    roleid = GetUserId();
+
+    LWLockAcquire(gatekeeper_state->lock, LW_EXCLUSIVE);
+    counters = gatekeeper_state->denial_counters;
+    for (i = 0; i < MAX_DENIAL_COUNTERS; i++)
+    {
+        if (counters[i].roleid == roleid)
+        {
+            counter = &counters[i];
+            break;
+        }
+        /* remember the free or oldest slot in case the role isn't tracked yet */
+        if (counter == NULL || !OidIsValid(counters[i].roleid) ||
+            (OidIsValid(counter->roleid) && counters[i].window_start < counter->window_start))
+            counter = &counters[i];
+    }
+
+    if (counter->roleid != roleid ||
+        TimestampDifferenceExceeds(counter->window_start, now, anomaly_window * 1000))
+    {
+        counter->roleid = roleid;
+        counter->window_start = now;
+        counter->count = 0;
+    }
+
+    count = ++counter->count;
+    if (count >= anomaly_threshold)
+    {
+        counter->window_start = now;
+        counter->count = 0;
+    }
+    LWLockRelease(gatekeeper_state->lock);
+
+    /* the audit record is written without holding the lock */
+    if (count >= anomaly_threshold)
+        gatekeeper_audit_log(WARNING, statement_type, "anomaly", "anomaly",
+                             psprintf("%d denials within %d seconds", count, anomaly_window));
+}
+
+/* returns true if the current user is one of the reserved roles and we are not
+ * executing on behalf of someone else (extension script, security definer, etc)
+ */
+static bool
+is_reserved_caller(void)
+{
+    if (creating_extension || is_security_restricted() || is_elevated())
+        return false;
+
+    /* only statements sent by the client, not ones issued from inside functions,
+     * procedures or triggers, so code that happens to run as a reserved role
+     * doesn't inherit its exemptions
+     */
+    if (current_utility_context != PROCESS_UTILITY_TOPLEVEL)
+        return false;
+
+    return is_current_user_reserved();
+}
+
+/* returns true if the current user is one of the reserved roles, regardless of the context.
+ * The result is kept for the current user id, SET ROLE and SET SESSION AUTHORIZATION
+ * change that id and so resolve it again. Outside a transaction the database scoped
+ * entries can't be resolved, such a result is not kept.
+ */
+static bool
+is_current_user_reserved(void)
+{
+    Oid roleid = GetUserId();
+
+    if (OidIsValid(reserved_status_roleid) && reserved_status_roleid == roleid)
+        return reserved_status;
+
+    reserved_status = allow_superuser_role(GetUserNameFromId(roleid, false));
+    reserved_status_roleid = IsTransactionState() ? roleid : InvalidOid;
+    return reserved_status;
+}
+
+/* roles exempt from all utility statement checks, eg: the control plane of the managed service.
+ * Only when the role is the session role itself, SET ROLE or a SECURITY DEFINER function owned
+ * by the role doesn't exempt other roles. Never in strict mode.
+ */
+static bool
+is_bypass_role(void)
+{
+    Oid roleid = GetUserId();
+
+    if (bypass_roles == NULL || bypass_roles[0] == '\0')
+        return false;
+    if (is_strict_mode_enabled() || is_security_restricted())
+        return false;
+    if (roleid != GetSessionUserId())
+        return false;
+    /* the role name lookup needs catalog access, eg: not for ROLLBACK of a failed transaction */
+    if (!IsTransactionState())
+        return false;
+
+    return identifier_list_contains(bypass_roles, GetUserNameFromId(roleid, false));
+}
+
+/* a renamed or dropped role can change what the remembered status should be */
+static void
+reserved_status_invalidate(Datum arg, int cacheid, uint32 hashvalue)
+{
+    reserved_status_roleid = InvalidOid;
+}
+
+/* the reserved roles setting merged with the entries from aiven.pg_security_config_file */
+static char *
+reserved_roles_setting(void)
+{
+    return merge_reserved_roles_setting(allowed_superuser_roles, reserved_roles_from_file);
+}
+
+static char *
+merge_reserved_roles_setting(const char *setting, const char *from_file)
+{
+    if (setting == NULL)
+        setting = "";
+    if (from_file == NULL)
+        return pstrdup(setting);
+
+    return psprintf("%s,%s", setting, from_file);
+}
+
+/* the roles of a merged reserved roles setting.
+ * strict mode with an empty list would lock out every superuser operation, including
+ * legitimate bootstrap ones, so in that case fall back to postgres (warned about at startup)
+ */
+static List *
+resolve_reserved_roles(const char *setting)
+{
+    List *allowed_superuser_list = NIL;
+
+    split_identifier_list(setting, &allowed_superuser_list, NULL);
+
+    if (allowed_superuser_list == NIL && pg_security_agent_strict)
+        allowed_superuser_list = list_make1(pstrdup(DEFAULT_RESERVED_ROLE));
+
+    return allowed_superuser_list;
+}
+
+/* returns the reserved roles that are in effect */
+static List *
+get_reserved_roles(void)
+{
+    return resolve_reserved_roles(reserved_roles_setting());
+}
+
+/* a ~regex entry has to match the whole role name. Always compiled with the C collation,
+ * which doesn't need catalog access, so patterns can be checked at postmaster startup.
+ */
+static int
+compile_role_regex(const char *pattern, regex_t *regex)
+{
+    char *anchored = psprintf("^(?:%s)$", pattern);
+    int len = strlen(anchored);
+    pg_wchar *wpattern = (pg_wchar *)palloc((len + 1) * sizeof(pg_wchar));
+    int wlen = pg_mb2wchar_with_len(anchored, wpattern, len);
+    int rc;
+
+    rc = pg_regcomp(regex, wpattern, wlen, REG_ADVANCED | REG_NOSUB, C_COLLATION_OID);
+    pfree(wpattern);
+    pfree(anchored);
+    return rc;
+}
+
+/* returns a description of the first ~regex entry that doesn't compile, NULL if all do */
+static char *
+invalid_role_regex(List *elemlist)
+{
+    ListCell *cell;
+
+    foreach (cell, elemlist)
+    {
+        char *entry = (char *)lfirst(cell);
+        regex_t regex;
+        char errstr[100];
+        int rc;
+
+        if (entry[0] != '~')
+            continue;
+
+        rc = compile_role_regex(entry + 1, &regex);
+        if (rc != REG_OKAY)
+        {
+            pg_regerror(rc, &regex, errstr, sizeof(errstr));
+            return psprintf("Invalid regular expression in entry \"%s\": %s.", entry, errstr);
+        }
+        pg_regfree(&regex);
+    }
+    return NULL;
+}
+
+static void
+role_regex_cache_reset(void)
+{
+    ListCell *cell;
+
+    foreach (cell, role_regex_cache)
+    {
+        RoleRegex *cached = (RoleRegex *)lfirst(cell);
+
+        pg_regfree(&cached->regex);
+        pfree(cached->pattern);
+        pfree(cached);
+    }
+    list_free(role_regex_cache);
+    role_regex_cache = NIL;
+}
+
+static bool
+role_regex_matches(const char *pattern, const char *target_role)
+{
+    ListCell *cell;
+    RoleRegex *regex = NULL;
+    MemoryContext oldcontext;
+    pg_wchar *wname;
+    int len;
+    int wlen;
+    int rc;
+
+    foreach (cell, role_regex_cache)
+    {
+        RoleRegex *cached = (RoleRegex *)lfirst(cell);
+
+        if (strcmp(cached->pattern, pattern) == 0)
+        {
+            regex = cached;
+            break;
+        }
+    }
+
+    if (regex == NULL)
+    {
+        /* the compiled regex outlives the statement, keep it in TopMemoryContext */
+        oldcontext = MemoryContextSwitchTo(TopMemoryContext);
+        regex = (RoleRegex *)palloc(sizeof(RoleRegex));
+        /* the patterns were checked when the setting changed */
+        if (compile_role_regex(pattern, &regex->regex) != REG_OKAY)
+        {
+            pfree(regex);
+            MemoryContextSwitchTo(oldcontext);
+            return false;
+        }
+        regex->pattern = pstrdup(pattern);
+        role_regex_cache = lappend(role_regex_cache, regex);
+        MemoryContextSwitchTo(oldcontext);
+    }
+
+    len = strlen(target_role);
+    wname = (pg_wchar *)palloc((len + 1) * sizeof(pg_wchar));
+    wlen = pg_mb2wchar_with_len(target_role, wname, len);
+    rc = pg_regexec(&regex->regex, wname, wlen, 0, NULL, 0, NULL, 0);
+    pfree(wname);
+
+    return rc == REG_OKAY;
+}
+
+/* check if a single reserved roles entry matches the role in the given database.
+ * entries are either a plain role name, applying to all databases, or scoped
+ * to a single database using db:role. dbname can be NULL when unknown, in which
+ * case only the global entries match.
+ */
+static bool
+reserved_role_entry_matches(const char *entry, const char *target_role, const char *dbname)
+{
+    const char *separator = strchr(entry, ':');
+
+    /* a regex may contain a ':' of its own, regex entries aren't database scoped */
+    if (entry[0] == '~')
+        return role_regex_matches(entry + 1, target_role);
+
+    if (separator == NULL)
+        return strcmp(target_role, entry) == 0;
+
+    if (dbname == NULL || strlen(dbname) != (size_t)(separator - entry) ||
+        strncmp(entry, dbname, separator - entry) != 0)
+        return false;
+
+    return strcmp(target_role, separator + 1) == 0;
+}
+
+/* check if a target role is in the list of roles that are permitted to have superuser */
+static bool
+allow_superuser_role(const char *target_role)
+{
+    List *allowed_superuser_list;
+    ListCell *role;
+    char *dbname = NULL;
+
+    if (target_role == NULL)
+        return false;
+
+    allowed_superuser_list = get_reserved_roles();
+
+    /* database scoped entries need the current database, which needs catalog access */
+    if (IsTransactionState() && OidIsValid(MyDatabaseId))
+        dbname = get_database_name(MyDatabaseId);
+
+    foreach (role, allowed_superuser_list)
+    {
+        char *allowed_role = (char *)lfirst(role);
+        if (reserved_role_entry_matches(allowed_role, target_role, dbname))
+        {
+            list_free(allowed_superuser_list);
+            return true;
+        }
+    }
+    list_free(allowed_superuser_list);
+    return false;
+}
+
+/* role options as they appear in the DefElem list, with the attribute keyword used
+ * in aiven.pg_security_protected_role_attributes. Boolean attributes only count as
+ * a modification when they are being turned on.
+ */
+static const struct
+{
+    const char *defname;
+    const char *attribute;
+    bool is_bool;
+} role_attributes[] = {
+    {"superuser", "superuser", true},
+    {"createdb", "createdb", true},
+    {"createrole", "createrole", true},
+    {"inherit", "inherit", true},
+    {"canlogin", "login", true},
+    {"isreplication", "replication", true},
+    {"bypassrls", "bypassrls", true},
+    {"password", "password", false},
+    {"connectionlimit", "connection_limit", false},
+    {"validUntil", "valid_until", false},
+};
+static const int NUM_ROLE_ATTRIBUTES = sizeof role_attributes / sizeof role_attributes[0];
+
+/* returns the attribute changed by the role option, turned on or off, or NULL if
+ * the option isn't a role attribute
+ */
+static const char *
+role_option_attribute(DefElem *defel)
+{
+    int i;
+
+    for (i = 0; i < NUM_ROLE_ATTRIBUTES; i++)
+    {
+        if (strcmp(defel->defname, role_attributes[i].defname) == 0)
+            return role_attributes[i].attribute;
+    }
+    return NULL;
+}
+
+/* returns the attribute modified by the role option, or NULL if the option isn't
+ * a role attribute or turns a boolean attribute off
+ */
+static const char *
+role_attribute_name(DefElem *defel)
+{
+    int i;
+
+    for (i = 0; i < NUM_ROLE_ATTRIBUTES; i++)
+    {
+        if (strcmp(defel->defname, role_attributes[i].defname) != 0)
+            continue;
+        if (role_attributes[i].is_bool && !defGetBoolean(defel))
+            return NULL;
+        return role_attributes[i].attribute;
+    }
+    return NULL;
+}
+
+/* returns the reason the role modification is not allowed in the current context, or NULL.
+ * attribute is the role attribute being modified, modifications of attributes not in
+ * aiven.pg_security_protected_role_attributes are always allowed. superuser is always
+ * protected. NULL means the whole role is being granted/altered and is always checked.
+ */
+static char *
+allow_role_stmt(const char *attribute)
+{
+    if (attribute != NULL &&
+        strcmp(attribute, "superuser") != 0 &&
+        !identifier_list_contains(protected_role_attributes, attribute))
+        return NULL;
+
+    if (is_strict_mode_enabled())
+        return "ROLE modification to SUPERUSER/privileged role not allowed";
+
+    if (creating_extension)
+        return "ROLE modification to SUPERUSER/privileged role not allowed in extensions";
+
+    if (is_security_restricted())
+        return "ROLE modification to SUPERUSER/privileged role not allowed in SECURITY_RESTRICTED_OPERATION";
+
+    if (is_elevated())
+        return "ROLE modification to SUPERUSER/privileged role not allowed";
+
+    return NULL;
+}
+
+/* resolves the name of a role for error messages and the superuser list,
+ * also covers CURRENT_USER/SESSION_USER where rolename is not set
+ */
+static char *
+rolespec_display_name(RoleSpec *role)
+{
+    Oid roleoid;
+
+    if (role->roletype == ROLESPEC_CSTRING)
+        return role->rolename;
+
+    roleoid = get_rolespec_oid(role, true);
+    if (OidIsValid(roleoid))
+        return GetUserNameFromId(roleoid, false);
+
+    return "unknown";
+}
+
+/* the command part of a COPY PROGRAM string, for the error message.
+ * arguments are left out as they can carry credentials, and the length is capped.
+ */
+static char *
+copy_program_display_name(const char *program)
+{
+    int len = strcspn(program, " \t\r\n");
+
+    return pnstrdup(program, pg_mbcliplen(program, len, 64));
+}
+
+/* parse a comma-separated identifier list GUC, same as SplitIdentifierString but empty
+ * entries (eg: "postgres,,admin,") are dropped instead of making the whole list invalid,
+ * and duplicates are only returned once. The number of dropped duplicates is returned
+ * in duplicates, when not NULL. A NULL value is an empty list.
+ */
+static bool
+split_identifier_list(const char *value, List **elemlist, int *duplicates)
+{
+    char *cleaned;
+    List *rawlist = NIL;
+    ListCell *cell;
+    ListCell *seen;
+    bool found;
+    int dups = 0;
+
+    *elemlist = NIL;
+    if (duplicates != NULL)
+        *duplicates = 0;
+    if (value == NULL)
+        return true;
+
+    /* SplitIdentifierString modifies the string and the list points into it, keep it around */
+    cleaned = palloc(strlen(value) + 1);
+    gatekeeper_strip_empty_list_entries(value, cleaned);
+
+    if (!SplitIdentifierString(cleaned, ',', &rawlist))
+    {
+        list_free(rawlist);
+        return false;
+    }
+
+    foreach (cell, rawlist)
+    {
+        found = false;
+        foreach (seen, *elemlist)
+        {
+            if (strcmp((char *)lfirst(seen), (char *)lfirst(cell)) == 0)
+            {
+                found = true;
+                break;
+            }
+        }
+        if (found)
+            dups++;
+        else
+            *elemlist = lappend(*elemlist, lfirst(cell));
+    }
+    list_free(rawlist);
+
+    if (duplicates != NULL)
+        *duplicates = dups;
+    return true;
+}
+
+/* check if a comma-separated identifier list GUC contains the name */
+static bool
+identifier_list_contains(const char *list_value, const char *name)
+{
+    List *elemlist = NIL;
+    ListCell *cell;
+    bool found = false;
+
+    if (list_value == NULL || name == NULL)
+        return false;
+
+    split_identifier_list(list_value, &elemlist, NULL);
+    foreach (cell, elemlist)
+    {
+        if (strcmp((char *)lfirst(cell), name) == 0)
+        {
+            found = true;
+            break;
+        }
+    }
+    list_free(elemlist);
+    return found;
+}
+
+/* returns the schema name if the relation lives in one of the protected schemas, otherwise NULL */
+static char *
+protected_relation_schema(RangeVar *relation)
+{
+    Oid relid;
+    char *schema_name;
+
+    /* no lock, postgres takes the proper lock when executing the statement */
+    relid = RangeVarGetRelid(relation, NoLock, true);
+    if (!OidIsValid(relid))
+        return NULL;
+
+    schema_name = get_namespace_name(get_rel_namespace(relid));
+    if (identifier_list_contains(protected_schemas, schema_name))
+        return schema_name;
+
+    return NULL;
+}
+
+/* make a COPY file name or allowed path prefix absolute and lexically normalised,
+ * relative paths are resolved against the data directory, same as COPY does
+ */
+static char *
+copy_absolute_path(const char *path)
+{
+    char *result;
+
+    if (is_absolute_path(path))
+        result = pstrdup(path);
+    else
+        result = psprintf("%s/%s", DataDir, path);
+    canonicalize_path(result);
+    return result;
+}
+
+/* resolve the symbolic links of an absolute path, for a file that doesn't exist yet those of
+ * the directory it would be created in. NULL when that directory doesn't exist either.
+ */
+static char *
+resolve_copy_path(const char *path)
+{
+    char *resolved;
+    char *parent;
+    char *result;
+
+    resolved = realpath(path, NULL);
+    if (resolved == NULL && errno == ENOENT)
+    {
+        parent = pstrdup(path);
+        get_parent_directory(parent);
+        resolved = realpath(parent[0] != '\0' ? parent : "/", NULL);
+        pfree(parent);
+        if (resolved == NULL)
+            return NULL;
+        result = psprintf("%s/%s", resolved, last_dir_separator(path) + 1);
+        free(resolved);
+        canonicalize_path(result);
+        return result;
+    }
+    if (resolved == NULL)
+        return NULL;
+
+    result = pstrdup(resolved);
+    free(resolved);
+    return result;
+}
+
+/* check if the COPY file is inside one of the aiven.pg_security_copy_allowed_paths directories */
+static bool
+copy_path_allowed(const char *filename)
+{
+    char *rawstring;
+    List *elemlist;
+    ListCell *cell;
+    char *path;
+    char *prefix;
+    bool allowed = false;
+
+    if (copy_allowed_paths == NULL || copy_allowed_paths[0] == '\0')
+        return false;
+
+    /* canonicalize_path resolves what it can of "..", anything left would
+     * climb out of the prefix, so never allow it
+     */
+    path = copy_absolute_path(filename);
+    if (path_contains_parent_reference(path))
+        return false;
+    /* compare where the file really is, a symbolic link would otherwise lead out of the directory */
+    path = resolve_copy_path(path);
+    if (path == NULL)
+        return false;
+
+    rawstring = pstrdup(copy_allowed_paths);
+    if (!SplitDirectoriesString(rawstring, ',', &elemlist))
+    {
+        list_free_deep(elemlist);
+        return false;
+    }
+
+    foreach (cell, elemlist)
+    {
+        prefix = copy_absolute_path((char *)lfirst(cell));
+        if (path_contains_parent_reference(prefix))
+            continue;
+        prefix = resolve_copy_path(prefix);
+        if (prefix != NULL && path_is_prefix_of_path(prefix, path))
+        {
+            allowed = true;
+            break;
+        }
+    }
+    list_free_deep(elemlist);
+    return allowed;
+}
+
+/* block COPY TO of the columns listed in aiven.pg_security_sensitive_columns,
+ * COPY without a column list includes every column of the table
+ */
+static void
+copy_sensitive_column_checks(CopyStmt *copyStmt)
+{
+    List *elemlist;
+    ListCell *cell;
+    ListCell *attCell;
+    Oid relid;
+    char *schema_name;
+    char *table_name;
+    char *schema;
+    char *table;
+    char *column;
+    bool included;
+
+    /* only reading data out of the table, COPY (query) TO isn't inspected */
+    if (copyStmt->is_from || copyStmt->relation == NULL)
+        return;
+    if (sensitive_columns == NULL || sensitive_columns[0] == '\0')
+        return;
+
+    relid = RangeVarGetRelid(copyStmt->relation, NoLock, true);
+    if (!OidIsValid(relid))
+        return;
+    schema_name = get_namespace_name(get_rel_namespace(relid));
+    table_name = get_rel_name(relid);
+
+    split_identifier_list(sensitive_columns, &elemlist, NULL);
+    foreach (cell, elemlist)
+    {
+        if (!gatekeeper_parse_sensitive_column((char *)lfirst(cell), &schema, &table, &column))
+            continue;
+        if (strcmp(schema, schema_name) != 0 || strcmp(table, table_name) != 0)
+            continue;
+
+        if (copyStmt->attlist == NIL)
+            included = get_attnum(relid, column) != InvalidAttrNumber;
+        else
+        {
+            included = false;
+            foreach (attCell, copyStmt->attlist)
+            {
+                if (strcmp(strVal(lfirst(attCell)), column) == 0)
+                {
+                    included = true;
+                    break;
+                }
+            }
+        }
+        if (!included)
+            continue;
+
+        if (deny_unless_reserved("COPY", "COPY of sensitive column %s.%s.%s not allowed", schema, table, column))
+        {
+            list_free(elemlist);
+            return;
+        }
+    }
+    list_free(elemlist);
+}
+
+static void
+allow_granted_roles(List *addroleto)
+{
+    ListCell *role_cell;
+    RoleSpec *rolemember;
+    Oid role_member_oid;
+    char *result;
+    // check if any of the roles we are trying to add to have superuser
+    foreach (role_cell, addroleto)
+    {
+        rolemember = lfirst(role_cell);
+        role_member_oid = get_rolespec_oid(rolemember, false);
+        result = allow_grant_or_alter_role(role_member_oid);
+        if (result != NULL)
+        {
+            gatekeeper_deny("CREATE ROLE", restricted_context_reason(), "%s (role %s)", result, rolespec_display_name(rolemember));
+            return;
+        }
+    }
+}
+
+/* check if the role is a superuser or a member of one of the privileged roles */
+static bool
+is_restricted_role(Oid role_oid)
+{
+    /* we first need to fetch the oid's of the reserved roles.
+     * these would be nice to pull from header files, but the required
+     * headers are generated using src/backend/catalog/genbki.pl and aren't guaranteed to exist.
+     */
+    Oid role_pg_execute_server_program;
+    Oid role_pg_read_server_files;
+    Oid role_pg_write_server_files;
+
+    role_pg_execute_server_program = get_role_oid("pg_execute_server_program", true);
+    role_pg_read_server_files = get_role_oid("pg_read_server_files", true);
+    role_pg_write_server_files = get_role_oid("pg_write_server_files", true);
+
+    return superuser_arg(role_oid) ||
+           is_member_of_role(role_oid, role_pg_execute_server_program) ||
+           is_member_of_role(role_oid, role_pg_read_server_files) ||
+           is_member_of_role(role_oid, role_pg_write_server_files);
+}
+
+/* the predefined role giving access to the server files or programs that granting
+ * role_oid hands out, either the predefined role itself or a role that is a member of it.
+ * NULL when there is none.
+ */
+static const char *
+server_access_role(Oid role_oid)
+{
+    static const char *server_access_roles[] = {"pg_execute_server_program", "pg_read_server_files", "pg_write_server_files"};
+    Oid predefined_oid;
+    int i;
+
+    for (i = 0; i < lengthof(server_access_roles); i++)
+    {
+        predefined_oid = get_role_oid(server_access_roles[i], true);
+        if (OidIsValid(predefined_oid) && is_member_of_role(role_oid, predefined_oid))
+            return server_access_roles[i];
+    }
+    return NULL;
+}
+
+/* membership of the server access roles is equivalent to file or command access on the
+ * server. It can only be given to the reserved roles, and to nobody in strict mode.
+ */
+static void
+server_access_grant_checks(const char *statement_type, Oid granted_oid, const char *granted_name, const char *grantee_name)
+{
+    const char *predefined_name = server_access_role(granted_oid);
+
+    if (predefined_name == NULL)
+        return;
+
+    if (is_strict_mode_enabled())
+        gatekeeper_deny(statement_type, "strict_mode", "granting role %s (%s) to role %s not allowed", granted_name, predefined_name, grantee_name);
+    else if (!allow_superuser_role(grantee_name))
+        gatekeeper_deny(statement_type, "grantee_not_reserved", "granting role %s (%s) to non-reserved role %s not allowed", granted_name, predefined_name, grantee_name);
+}
+
+/* ADMIN OPTION on a role allows granting it further, eg: GRANT postgres TO app WITH ADMIN OPTION.
+ * Only the reserved roles can get it for one of the reserved roles, in strict mode nobody can.
+ */
+static void
+admin_option_grant_checks(const char *statement_type, const char *granted_name, const char *grantee_name)
+{
+    if (!allow_superuser_role(granted_name))
+        return;
+
+    if (is_strict_mode_enabled())
+        gatekeeper_deny(statement_type, "strict_mode", "granting ADMIN OPTION on reserved role %s to role %s not allowed", granted_name, grantee_name);
+    else if (!allow_superuser_role(grantee_name))
+        gatekeeper_deny(statement_type, "grantee_not_reserved", "granting ADMIN OPTION on reserved role %s to non-reserved role %s not allowed", granted_name, grantee_name);
+}
+
+/* PG16 replaced the admin_opt flag with a list of options, WITH ADMIN TRUE/FALSE */
+static bool
+grant_role_admin_option(GrantRoleStmt *grantRoleStmt)
+{
+#if PG16_GTE
+    ListCell *option;
+    DefElem *defel;
+
+    foreach (option, grantRoleStmt->opt)
+    {
+        defel = (DefElem *)lfirst(option);
+        if (strcmp(defel->defname, "admin") == 0)
+            return defGetBoolean(defel);
+    }
+    return false;
+#else
+    return grantRoleStmt->admin_opt;
+#endif
+}
+
+static char *
+allow_grant_or_alter_role(Oid role_oid)
+{
+    /* check if we are trying to alter a reserved (privileged) role, or grant
+     * access to superuser or privileged roles
+     */
+    if (is_restricted_role(role_oid))
+    {
+        return allow_role_stmt(NULL);
+    }
+    return NULL;
+}
+
+/* with aiven.pg_security_enforce_password_policy, passwords have to be sent already
+ * hashed (eg: psql \password), so they never show up in plain text in the statement or
+ * the logs, and only reserved roles can set the password of a reserved role
+ */
+static void
+password_policy_checks(const char *statement_type, const char *rolename, DefElem *defel)
+{
+    char *password;
+
+    if (!enforce_password_policy || strncmp(defel->defname, "password", 9) != 0)
+        return;
+    /* PASSWORD NULL removes the password */
+    if (defel->arg == NULL)
+        return;
+
+    password = defGetString(defel);
+    if (get_password_type(password) == PASSWORD_TYPE_PLAINTEXT)
+    {
+        gatekeeper_deny(statement_type, "plaintext_password", "setting a plain text password not allowed, use an encrypted password (role %s)", rolename);
+        return;
+    }
+    if (allow_superuser_role(rolename) && !is_reserved_caller())
+    {
+        gatekeeper_deny(statement_type, "caller_not_reserved", "setting the password of a reserved role not allowed (role %s)", rolename);
+        return;
+    }
+}
+
+/* the checks are dispatched on the node tag, so the cast in each check should always
+ * be right. Skip the check with a warning instead of reading a node of the wrong type
+ * if that ever stops being true, eg: a check registered for the wrong tag.
+ */
+static bool
+node_type_matches(Node *stmt, NodeTag expected, const char *check_name)
+{
+    if (stmt != NULL && nodeTag(stmt) == expected)
+        return true;
+
+    elog(WARNING, "aiven_gatekeeper: %s called with node type %d instead of %d, check skipped",
+         check_name, stmt ? (int)nodeTag(stmt) : -1, (int)expected);
+    return false;
+}
+
+/* a role named like a reserved role is treated as one, whether or not it is a superuser.
+ * Creating a role under a reserved name that doesn't exist yet, or renaming a role to it,
+ * would hand out the reserved role exemptions, so that is only allowed for the reserved
+ * roles, and nobody in strict mode.
+ */
+static void
+reserved_name_checks(const char *statement_type, const char *rolename)
+{
+    if (!allow_superuser_role(rolename))
+        return;
+
+    deny_unless_reserved(statement_type, "role name %s is a reserved role name", rolename);
+}
+
+/* names of the roles created or renamed by non-reserved roles have to match
+ * aiven.pg_security_role_name_pattern, eg: app_.* to keep them apart from the managed roles
+ */
+static void
+role_name_policy_checks(const char *statement_type, const char *rolename)
+{
+    if (role_name_pattern == NULL || role_name_pattern[0] == '\0')
+        return;
+    if (is_reserved_caller())
+        return;
+
+    if (!role_regex_matches(role_name_pattern, rolename))
+        gatekeeper_deny(statement_type, "role_name_policy", "role name %s doesn't match the required pattern %s", rolename, role_name_pattern);
+}
+
+/* ALTER ROLE ... RENAME TO, other renames are unchecked statements for the default deny */
+static void
+rename_role_checks(Node *stmt)
+{
+    RenameStmt *renameStmt = (RenameStmt *)stmt;
+
+    if (!node_type_matches(stmt, T_RenameStmt, "rename_role_checks"))
+        return;
+
+    if (renameStmt->renameType != OBJECT_ROLE)
+    {
+        default_deny_checks(stmt);
+        return;
+    }
+
+    reserved_name_checks("ALTER ROLE", renameStmt->newname);
+    role_name_policy_checks("ALTER ROLE", renameStmt->newname);
+}
+
+/* ALTER ROLE */
+static void
+alter_role_checks(Node *stmt)
+{
+    AlterRoleStmt *alterRoleStmt = (AlterRoleStmt *)stmt;
+    ListCell *option;
+    DefElem *defel;
+    Oid roleoid;
+    const char *attribute;
+    char *result;
+    bool superuser_allowed;
+
+    if (!node_type_matches(stmt, T_AlterRoleStmt, "alter_role_checks"))
+        return;
+
+    // check we aren't altering a reserved role (existing superuser)
+    roleoid = get_rolespec_oid(alterRoleStmt->role, true);
+    result = allow_grant_or_alter_role(roleoid);
+    if (result != NULL)
+        gatekeeper_deny("ALTER ROLE", restricted_context_reason(), "%s (role %s)", result, rolespec_display_name(alterRoleStmt->role));
+
+    // every option is checked against a few lists, bound the work for a crafted statement
+    if (list_length(alterRoleStmt->options) > max_role_options)
+    {
+        gatekeeper_deny("ALTER ROLE", "too_many_options", "ALTER ROLE %s with %d options not allowed, the limit is %d",
+                        rolespec_display_name(alterRoleStmt->role), list_length(alterRoleStmt->options), max_role_options);
+        return;
+    }
+
+    // check if we are altering with superuser
+    foreach (option, alterRoleStmt->options)
+    {
+        defel = (DefElem *)lfirst(option);
+
+        // a role granting itself superuser, replication or bypassrls is never allowed,
+        // unless it is one of the reserved roles. This doesn't depend on the target
+        // currently being a restricted role.
+        if (OidIsValid(roleoid) && roleoid == GetUserId() &&
+            (strncmp(defel->defname, "superuser", 10) == 0 ||
+             strncmp(defel->defname, "isreplication", 14) == 0 ||
+             strncmp(defel->defname, "bypassrls", 10) == 0) &&
+            defGetBoolean(defel) && !is_reserved_caller())
+            gatekeeper_deny("ALTER ROLE", "self_escalation", "altering own %s attribute not allowed (role %s)", defel->defname, rolespec_display_name(alterRoleStmt->role));
+
+        // superuser or nosuperuser is supplied (both are treated as defname superuser) and check that the arg is set to true
+        if (strncmp(defel->defname, "superuser", 10) == 0 && defGetBoolean(defel))
+        {
+            // regardless of context (elevated privilege or not), check if the target role is allowed to be superuser
+            superuser_allowed = allow_superuser_role(rolespec_display_name(alterRoleStmt->role));
+            if (!superuser_allowed)
+                gatekeeper_deny("ALTER ROLE", "role_not_reserved", "Role %s not in permitted superuser list", rolespec_display_name(alterRoleStmt->role));
+            privileged_statement_checks("ALTER ROLE", psprintf("granting SUPERUSER to role %s", rolespec_display_name(alterRoleStmt->role)));
+            // a role that already is a superuser doesn't add one
+            if (superuser_allowed && !denials_pending() && OidIsValid(roleoid) && !superuser_arg(roleoid))
+                gatekeeper_audit_log(LOG, "ALTER ROLE", "superuser_added", "allow", psprintf("SUPERUSER granted to role %s", rolespec_display_name(alterRoleStmt->role)));
+        }
+
+        password_policy_checks("ALTER ROLE", rolespec_display_name(alterRoleStmt->role), defel);
+
+        // in strict mode the passwords of the reserved roles are rotated out of band,
+        // whether or not the role currently is a superuser. PASSWORD NULL counts too.
+        if (strncmp(defel->defname, "password", 9) == 0 && is_strict_mode_enabled() &&
+            allow_superuser_role(rolespec_display_name(alterRoleStmt->role)))
+            gatekeeper_deny("ALTER ROLE", "strict_mode", "changing the password of reserved role %s not allowed", rolespec_display_name(alterRoleStmt->role));
+
+        // disabling LOGIN on a critical service role, eg: the replication or monitoring role,
+        // causes an outage. Only the reserved roles can do that, nobody in strict mode.
+        if (strncmp(defel->defname, "canlogin", 9) == 0 && !defGetBoolean(defel) &&
+            identifier_list_contains(critical_login_roles, rolespec_display_name(alterRoleStmt->role)))
+            deny_unless_reserved("ALTER ROLE", "disabling LOGIN of critical role %s not allowed", rolespec_display_name(alterRoleStmt->role));
+
+        // the guarded attributes of the reserved and critical roles, eg: INHERIT or CONNECTION LIMIT,
+        // can only be changed, in either direction, by the reserved roles. Nobody in strict mode.
+        attribute = role_option_attribute(defel);
+        if (attribute != NULL && identifier_list_contains(guarded_role_attributes, attribute) &&
+            (allow_superuser_role(rolespec_display_name(alterRoleStmt->role)) ||
+             identifier_list_contains(critical_login_roles, rolespec_display_name(alterRoleStmt->role))))
+            deny_unless_reserved("ALTER ROLE", "changing guarded attribute %s of role %s not allowed", attribute, rolespec_display_name(alterRoleStmt->role));
+
+        // check if the attribute being modified is protected in the current context
+        attribute = role_attribute_name(defel);
+        if (attribute != NULL)
+        {
+            result = allow_role_stmt(attribute);
+            if (result != NULL)
+                gatekeeper_deny("ALTER ROLE", restricted_context_reason(), "%s (role %s, attribute %s)", result, rolespec_display_name(alterRoleStmt->role), attribute);
+        }
+    }
+}
+
+/* CREATE ROLE */
+static void
+create_role_checks(Node *stmt)
+{
+    CreateRoleStmt *createRoleStmt = (CreateRoleStmt *)stmt;
+    ListCell *option;
+    ListCell *roleCell;
+    DefElem *defel;
+    List *addroleto;
+    const char *attribute;
+    char *result;
+    bool superuser_allowed;
+
+    if (!node_type_matches(stmt, T_CreateRoleStmt, "create_role_checks"))
+        return;
+
+    reserved_name_checks("CREATE ROLE", createRoleStmt->role);
+    role_name_policy_checks("CREATE ROLE", createRoleStmt->role);
+
+    // every option is checked against a few lists, bound the work for a crafted statement
+    if (list_length(createRoleStmt->options) > max_role_options)
+    {
+        gatekeeper_deny("CREATE ROLE", "too_many_options", "CREATE ROLE %s with %d options not allowed, the limit is %d",
+                        createRoleStmt->role, list_length(createRoleStmt->options), max_role_options);
+        return;
+    }
+
+    foreach (option, createRoleStmt->options)
+    {
+        defel = (DefElem *)lfirst(option);
+
+        // check if we are granting superuser
+        if (strncmp(defel->defname, "superuser", 10) == 0 && defGetBoolean(defel))
+        {
+            // regardless of context (elevated privilege or not), check if the target role is allowed to be superuser
+            superuser_allowed = allow_superuser_role(createRoleStmt->role);
+            if (!superuser_allowed)
+                gatekeeper_deny("CREATE ROLE", "role_not_reserved", "Role %s not in permitted superuser list", createRoleStmt->role);
+            privileged_statement_checks("CREATE ROLE", psprintf("granting SUPERUSER to role %s", createRoleStmt->role));
+            if (superuser_allowed && !denials_pending())
+                gatekeeper_audit_log(LOG, "CREATE ROLE", "superuser_added", "allow", psprintf("SUPERUSER granted to new role %s", createRoleStmt->role));
+        }
+
+        password_policy_checks("CREATE ROLE", createRoleStmt->role, defel);
+
+        // check if the attribute being set is protected in the current context
+        attribute = role_attribute_name(defel);
+        if (attribute != NULL)
+        {
+            result = allow_role_stmt(attribute);
+            if (result != NULL)
+                gatekeeper_deny("CREATE ROLE", restricted_context_reason(), "%s (role %s, attribute %s)", result, createRoleStmt->role, attribute);
+        }
+
+        // check if user is being added to a role that has superuser or other high privilege
+        // IN ROLE/IN GROUP is a membership grant at creation time, so it gets the same
+        // check as GRANT ROLE. ROLE/USER (rolemembers) adds existing roles as members of
+        // the new role instead, which does not give the new role any extra privilege.
+        if (strncmp(defel->defname, "addroleto", 10) == 0)
+        {
+            addroleto = (List *)defel->arg;
+            allow_granted_roles(addroleto);
+            foreach (roleCell, addroleto)
+                server_access_grant_checks("CREATE ROLE", get_rolespec_oid((RoleSpec *)lfirst(roleCell), false),
+                                           rolespec_display_name((RoleSpec *)lfirst(roleCell)), createRoleStmt->role);
+        }
+
+        // ADMIN adds existing roles as members of the new role WITH ADMIN OPTION, that only
+        // matters when the new role has a reserved role name
+        if (strncmp(defel->defname, "adminmembers", 13) == 0)
+        {
+            foreach (roleCell, (List *)defel->arg)
+                admin_option_grant_checks("CREATE ROLE", createRoleStmt->role, rolespec_display_name((RoleSpec *)lfirst(roleCell)));
+        }
+    }
+}
+
+/* GRANT ROLE */
+static void
+grant_role_checks(Node *stmt)
+{
+    GrantRoleStmt *grantRoleStmt = (GrantRoleStmt *)stmt;
+    ListCell *grantRoleCell;
+    ListCell *granteeCell;
+    AccessPriv *priv;
+    Oid roleoid;
+    char *result;
+    bool admin_option;
+
+    if (!node_type_matches(stmt, T_GrantRoleStmt, "grant_role_checks"))
+        return;
+
+    // REVOKE ADMIN OPTION FOR sets the same option, but only takes it away
+    admin_option = grantRoleStmt->is_grant && grant_role_admin_option(grantRoleStmt);
+
+    // check if any of the granted roles have superuser permission
+    foreach (grantRoleCell, grantRoleStmt->granted_roles)
+    {
+        priv = (AccessPriv *)lfirst(grantRoleCell);
+        roleoid = get_role_oid(priv->priv_name, false);
+        result = allow_grant_or_alter_role(roleoid);
+        if (result != NULL)
+            gatekeeper_deny("GRANT ROLE", restricted_context_reason(), "%s (role %s)", result, priv->priv_name);
+        if (is_restricted_role(roleoid))
+            privileged_statement_checks("GRANT ROLE", psprintf("granting role %s", priv->priv_name));
+
+        // revoking the server access roles takes access away, only check grants
+        if (grantRoleStmt->is_grant)
+        {
+            foreach (granteeCell, grantRoleStmt->grantee_roles)
+            {
+                server_access_grant_checks("GRANT ROLE", roleoid, priv->priv_name, rolespec_display_name((RoleSpec *)lfirst(granteeCell)));
+                if (admin_option)
+                    admin_option_grant_checks("GRANT ROLE", priv->priv_name, rolespec_display_name((RoleSpec *)lfirst(granteeCell)));
+            }
+        }
+    }
+}
+
+/* COPY ... FROM into pg_authid, pg_auth_members or pg_proc, from a file or STDIN, writes the
+ * catalog directly, same as the INSERT the executor checks deny
+ */
+static void
+copy_catalog_checks(CopyStmt *copyStmt)
+{
+    Oid relid;
+    const char *reason;
+
+    if (copyStmt->relation == NULL)
+        return;
+
+    relid = RangeVarGetRelid(copyStmt->relation, NoLock, true);
+    /* pg_authid, pg_auth_members, pg_proc. Unlike UPDATE of pg_proc, every column gets a value, so no column exemption */
+    if (relid != 1260 && relid != 1261 && relid != 1255)
+        return;
+
+    if (is_strict_mode_enabled() || !is_reserved_caller())
+    {
+        reason = restricted_context_reason();
+        if (reason == NULL)
+            reason = "caller_not_reserved";
+        gatekeeper_deny("COPY", reason, "COPY FROM into %s is only allowed for reserved roles", get_rel_name(relid));
+    }
+}
+
+/* strict mode still lets a COPY TO/FROM FILE in one of the allowed directories through,
+ * for the reserved roles and for roles with pg_read_server_files or pg_write_server_files.
+ * Not from an extension script, a security restricted operation or an elevated context.
+ */
+static bool
+copy_strict_path_allowed(CopyStmt *copyStmt)
+{
+    Oid file_role;
+
+    if (creating_extension || is_security_restricted() || is_elevated())
+        return false;
+    if (!copy_path_allowed(copyStmt->filename))
+        return false;
+    if (is_reserved_caller())
+        return true;
+
+#if PG14_GTE
+    file_role = copyStmt->is_from ? ROLE_PG_READ_SERVER_FILES : ROLE_PG_WRITE_SERVER_FILES;
+#else
+    file_role = copyStmt->is_from ? DEFAULT_ROLE_READ_SERVER_FILES : DEFAULT_ROLE_WRITE_SERVER_FILES;
+#endif
+    return has_privs_of_role(GetUserId(), file_role);
+}
+
+/* COPY */
+static void
+copy_stmt_checks(Node *stmt)
+{
+    /* get the actual copy statement so we can check is_program and filename */
+    CopyStmt *copyStmt = (CopyStmt *)stmt;
+    ListCell *option;
+    DefElem *defel;
+
+    if (!node_type_matches(stmt, T_CopyStmt, "copy_stmt_checks"))
+        return;
+
+    /* check if TO/FROM PROGRAM
+     * we deny this regardless of the context we are running in
+     */
+    if (copyStmt->is_program)
+    {
+        /* running a program is the one denial a monitor only build can be told to enforce */
+        if (copy_program_hard_block)
+            gatekeeper_hard_deny("COPY", "always", psprintf("COPY TO/FROM PROGRAM not allowed (program \"%s\")", copy_program_display_name(copyStmt->filename)));
+        else
+            gatekeeper_deny("COPY", "always", "COPY TO/FROM PROGRAM not allowed (program \"%s\")", copy_program_display_name(copyStmt->filename));
+        return;
+    }
+    /* TO/FROM PROGRAM is the only syntax setting is_program, a program option in
+     * WITH (...) is rejected by postgres as unrecognized. Deny it anyway, so a future
+     * option spelling can't get around the check above.
+     */
+    foreach (option, copyStmt->options)
+    {
+        defel = (DefElem *)lfirst(option);
+        if (strncmp(defel->defname, "program", 8) == 0)
+        {
+            if (copy_program_hard_block)
+                gatekeeper_hard_deny("COPY", "always", "COPY TO/FROM PROGRAM not allowed (program option)");
+            else
+                gatekeeper_deny("COPY", "always", "COPY TO/FROM PROGRAM not allowed (program option)");
+            return;
+        }
+    }
+
+    /* COPY FROM doesn't go through the executor checks, same rule for the catalogs */
+    if (copyStmt->is_from)
+        copy_catalog_checks(copyStmt);
+
+    /* sensitive columns can't be copied out, to a file or to the client */
+    copy_sensitive_column_checks(copyStmt);
+
+    /* otherwise, we don't want copy TO/FROM FILE
+     * in an elevated context
+     */
+    if (copyStmt->filename)
+    {
+        if (is_strict_mode_enabled() && !copy_strict_path_allowed(copyStmt))
+        {
+            gatekeeper_deny("COPY", "strict_mode", "COPY TO/FROM FILE not allowed (file \"%s\")", copyStmt->filename);
+            return;
+        }
+        if (creating_extension)
+        {
+            gatekeeper_deny("COPY", "creating_extension", "COPY TO/FROM FILE not allowed in extensions (file \"%s\")", copyStmt->filename);
+            return;
+        }
+        if (is_security_restricted())
+        {
+            gatekeeper_deny("COPY", "security_restricted", "COPY TO/FROM FILE not allowed in SECURITY_RESTRICTED_OPERATION (file \"%s\")", copyStmt->filename);
+            return;
+        }
+        /* the file is in one of the allowed directories, postgres still checks the
+         * role has pg_read_server_files/pg_write_server_files. Only exempts an elevated
+         * context, extension scripts and security restricted operations never write files.
+         */
+        if (copy_path_allowed(copyStmt->filename))
+        {
+            if (is_elevated() || is_strict_mode_enabled())
+                gatekeeper_audit_log(LOG, "COPY", "allowed_path", "allow", psprintf("COPY TO/FROM FILE in allowed path (file \"%s\")", copyStmt->filename));
+            return;
+        }
+        if (is_elevated())
+        {
+            gatekeeper_deny("COPY", "elevated", "COPY TO/FROM FILE not allowed (file \"%s\")", copyStmt->filename);
+            return;
+        }
+    }
+}
+
+/* check if the reserved roles may bypass the checks of a category listed in
+ * aiven.pg_security_reserved_bypass ("extension" or "function").
+ * Never in strict mode, and never in a security restricted or elevated context,
+ * the session itself has to be a reserved superuser. Running an extension script
+ * is allowed, that is what the function category is for.
+ */
+static bool
+reserved_bypass_allowed(const char *category)
+{
+    if (is_strict_mode_enabled())
+        return false;
+    if (!identifier_list_contains(reserved_bypass, category))
+        return false;
+    if (is_security_restricted() || is_elevated())
+        return false;
+
+    return superuser() && allow_superuser_role(GetUserNameFromId(GetSessionUserId(), false));
+}
+
+static bool
+is_untrusted_language(const char *language)
+{
+    int i;
+
+    for (i = 0; i < NUM_UNTRUSTED_LANGUAGES; i++)
+    {
+        if (strcmp(untrusted_languages[i], language) == 0)
+            return true;
+    }
+    return false;
+}
+
+/* CREATE OR REPLACE FUNCTION
+ * replacing the body of an existing function owned by a reserved or restricted role,
+ * or living in a protected schema, changes what trusted code does for everyone calling it.
+ * Only the reserved roles can do that, nobody in strict mode. An extension script
+ * replacing its own functions during an update is fine.
+ */
+static void
+replaced_function_checks(CreateFunctionStmt *createFuncStmt)
+{
+    ListCell *lc;
+    FunctionParameter *fp;
+    Oid *argtypes;
+    Oid typid;
+    Oid funcoid;
+    Oid owner;
+    HeapTuple proctup;
+    char *schema_name;
+    char *owner_name;
+    int nargs = 0;
+
+    if (!createFuncStmt->replace)
+        return;
+
+    /* resolve the existing function the same way CREATE OR REPLACE does,
+     * by name and input argument types
+     */
+    argtypes = (Oid *)palloc0(sizeof(Oid) * (list_length(createFuncStmt->parameters) + 1));
+    foreach (lc, createFuncStmt->parameters)
+    {
+        fp = (FunctionParameter *)lfirst(lc);
+        if (fp->mode == FUNC_PARAM_OUT || fp->mode == FUNC_PARAM_TABLE)
+            continue;
+        typid = LookupTypeNameOid(NULL, fp->argType, true);
+        /* an unknown argument type can't match an existing function */
+        if (!OidIsValid(typid))
+            return;
+        argtypes[nargs++] = typid;
+    }
+
+    funcoid = LookupFuncName(createFuncStmt->funcname, nargs, argtypes, true);
+    if (!OidIsValid(funcoid))
+        return;
+
+    if (creating_extension && getExtensionOfObject(ProcedureRelationId, funcoid) == CurrentExtensionObject)
+        return;
+
+    proctup = SearchSysCache1(PROCOID, ObjectIdGetDatum(funcoid));
+    if (!HeapTupleIsValid(proctup))
+        return;
+    owner = ((Form_pg_proc)GETSTRUCT(proctup))->proowner;
+    ReleaseSysCache(proctup);
+
+    schema_name = get_namespace_name(get_func_namespace(funcoid));
+    owner_name = GetUserNameFromId(owner, true);
+
+    if (!identifier_list_contains(protected_schemas, schema_name) &&
+        !(owner_name != NULL && allow_superuser_role(owner_name)) &&
+        !is_restricted_role(owner))
+        return;
+
+    deny_unless_reserved("CREATE FUNCTION", "replacing function %s is not allowed", NameListToString(createFuncStmt->funcname));
+}
+
+/* CREATE FUNCTION */
+static void
+create_function_checks(Node *stmt)
+{
+    CreateFunctionStmt *createFuncStmt = (CreateFunctionStmt *)stmt;
+    ListCell *option;
+    DefElem *defel;
+    char *funcLang;
+    int i;
+    bool checkBody;
+    bool securityDefiner = false;
+    bool bypass;
+    char *sqlBody = "";
+
+    if (!node_type_matches(stmt, T_CreateFunctionStmt, "create_function_checks"))
+        return;
+
+    checkBody = false; // used for versions prior to 14, where the sql_body is not availble in the CreateFuncStmt struct
+    replaced_function_checks(createFuncStmt);
+    /* only the context checks below can be bypassed, not the SECURITY DEFINER policy */
+    bypass = restricted_context_reason() != NULL && reserved_bypass_allowed("function");
+
+    foreach (option, createFuncStmt->options)
+    {
+        defel = (DefElem *)lfirst(option);
+
+        /* check if of language type internal
+         * this is not accessible to untrusted users, so disable if elevated context
+         */
+        if (strncmp(defel->defname, "language", 9) == 0)
+        {
+            funcLang = defGetString(defel);
+            /* check if restricted language type */
+            if (is_untrusted_language(funcLang) && bypass)
+            {
+                gatekeeper_audit_log(LOG, "CREATE FUNCTION", "reserved_bypass", "allow",
+                                     psprintf("LANGUAGE %s allowed for reserved role (function %s)", funcLang, NameListToString(createFuncStmt->funcname)));
+            }
+            else if (is_untrusted_language(funcLang))
+            {
+                if (is_strict_mode_enabled())
+                {
+                    gatekeeper_deny("CREATE FUNCTION", "strict_mode", "LANGUAGE %s not allowed (function %s)", funcLang, NameListToString(createFuncStmt->funcname));
+                    return;
+                }
+                if (creating_extension)
+                {
+                    gatekeeper_deny("CREATE FUNCTION", "creating_extension", "LANGUAGE %s not allowed in extensions (function %s)", funcLang, NameListToString(createFuncStmt->funcname));
+                    return;
+                }
+                if (is_security_restricted())
+                {
+                    gatekeeper_deny("CREATE FUNCTION", "security_restricted", "LANGUAGE %s not allowed in SECURITY_RESTRICTED_OPERATION (function %s)", funcLang, NameListToString(createFuncStmt->funcname));
+                    return;
+                }
+                if (is_elevated())
+                {
+                    gatekeeper_deny("CREATE FUNCTION", "elevated", "LANGUAGE %s not allowed (function %s)", funcLang, NameListToString(createFuncStmt->funcname));
+                    return;
+                }
+            }
+            else if (strncmp(funcLang, "internal", 9) == 0 && (is_strict_mode_enabled() || creating_extension || is_elevated() || is_security_restricted()))
+            {
+                checkBody = true;
+            }
+        }
+        /* extract the sql body so we can use it to check if restricted internal
+         * function is being declared
+         */
+        if (strncmp(defel->defname, "as", 3) == 0)
+        {
+            sqlBody = defGetString(defel);
+        }
+        /* SECURITY DEFINER, EXTERNAL SECURITY DEFINER */
+        if (strncmp(defel->defname, "security", 9) == 0)
+        {
+            securityDefiner = defGetBoolean(defel);
+        }
+    }
+    /* a SECURITY DEFINER function created by a superuser runs everything as superuser,
+     * only the reserved roles can create one, nobody in strict mode
+     */
+    if (block_security_definer && securityDefiner && superuser())
+    {
+        if (deny_unless_reserved("CREATE FUNCTION", "SECURITY DEFINER function owned by superuser not allowed (function %s)", NameListToString(createFuncStmt->funcname)))
+            return;
+    }
+    /* we need to check the sql body, as we are in restricted context and the function is of type internal*/
+    if (checkBody == true)
+    {
+        for (i = 0; i < num_reserved_funcs; i++)
+        {
+            /* internal names are case sensitive, so strcmp is fine here */
+            if (strncmp(reserved_func_list[i], sqlBody, 28) == 0)
+            {
+                if (bypass)
+                {
+                    gatekeeper_audit_log(LOG, "CREATE FUNCTION", "reserved_bypass", "allow",
+                                         psprintf("using builtin function %s allowed for reserved role (function %s)", sqlBody, NameListToString(createFuncStmt->funcname)));
+                    return;
+                }
+                gatekeeper_deny("CREATE FUNCTION", restricted_context_reason(), "using builtin function %s is not allowed (function %s)", sqlBody, NameListToString(createFuncStmt->funcname));
+                return;
+            }
+        }
+    }
+}
+
+/* DO
+ * an anonymous code block in an untrusted language runs the same code a function would,
+ * without ever going through CREATE FUNCTION
+ */
+static void
+do_stmt_checks(Node *stmt)
+{
+    DoStmt *doStmt = (DoStmt *)stmt;
+    ListCell *arg;
+    DefElem *defel;
+    char *language = "plpgsql"; // the default when LANGUAGE is left out
+
+    if (!node_type_matches(stmt, T_DoStmt, "do_stmt_checks"))
+        return;
+
+    foreach (arg, doStmt->args)
+    {
+        defel = (DefElem *)lfirst(arg);
+        if (strncmp(defel->defname, "language", 9) == 0)
+            language = defGetString(defel);
+    }
+
+    if (!is_untrusted_language(language) || restricted_context_reason() == NULL)
+        return;
+
+    if (reserved_bypass_allowed("function"))
+        gatekeeper_audit_log(LOG, "DO", "reserved_bypass", "allow", psprintf("DO with LANGUAGE %s allowed for reserved role", language));
+    else
+        gatekeeper_deny("DO", restricted_context_reason(), "DO with LANGUAGE %s not allowed", language);
+}
+
+/* CREATE TRIGGER
+ * a trigger runs its function for every row change on the table, by whoever makes it.
+ * The function gets the same language policy as CREATE FUNCTION and DO, and a SECURITY DEFINER
+ * function of a reserved or restricted role can only be attached by the reserved roles,
+ * nobody in strict mode. Other functions run with the privileges of the role changing the table.
+ */
+static void
+create_trigger_checks(Node *stmt)
+{
+    CreateTrigStmt *createTrigStmt = (CreateTrigStmt *)stmt;
+    HeapTuple proctup;
+    Form_pg_proc procForm;
+    Oid funcoid;
+    Oid owner;
+    Oid lang;
+    bool securityDefiner;
+    char *language;
+    char *owner_name;
+
+    if (!node_type_matches(stmt, T_CreateTrigStmt, "create_trigger_checks"))
+        return;
+
+    /* trigger functions don't take arguments, same lookup as CREATE TRIGGER itself */
+    funcoid = LookupFuncName(createTrigStmt->funcname, 0, NULL, true);
+    if (!OidIsValid(funcoid))
+        return;
+
+    proctup = SearchSysCache1(PROCOID, ObjectIdGetDatum(funcoid));
+    if (!HeapTupleIsValid(proctup))
+        return;
+    procForm = (Form_pg_proc)GETSTRUCT(proctup);
+    owner = procForm->proowner;
+    lang = procForm->prolang;
+    securityDefiner = procForm->prosecdef;
+    ReleaseSysCache(proctup);
+
+    language = get_language_name(lang, true);
+    if (language != NULL && is_untrusted_language(language) && restricted_context_reason() != NULL)
+    {
+        if (reserved_bypass_allowed("function"))
+            gatekeeper_audit_log(LOG, "CREATE TRIGGER", "reserved_bypass", "allow",
+                                 psprintf("trigger function %s in LANGUAGE %s allowed for reserved role (trigger %s)", NameListToString(createTrigStmt->funcname), language, createTrigStmt->trigname));
+        else
+        {
+            gatekeeper_deny("CREATE TRIGGER", restricted_context_reason(), "trigger function %s in LANGUAGE %s not allowed (trigger %s)", NameListToString(createTrigStmt->funcname), language, createTrigStmt->trigname);
+            return;
+        }
+    }
+
+    if (!securityDefiner)
+        return;
+    owner_name = GetUserNameFromId(owner, true);
+    if (!(owner_name != NULL && allow_superuser_role(owner_name)) && !is_restricted_role(owner))
+        return;
+
+    deny_unless_reserved("CREATE TRIGGER", "SECURITY DEFINER trigger function %s of role %s not allowed (trigger %s)", NameListToString(createTrigStmt->funcname), owner_name ? owner_name : "unknown", createTrigStmt->trigname);
+}
+
+/* ALTER FUNCTION ... SECURITY DEFINER
+ * turns an existing function into one running as its owner. For a function owned by a
+ * superuser, a role holding one of the privileged permissions or one of the reserved roles
+ * that is only allowed for the reserved roles, and nobody in strict mode.
+ */
+static void
+alter_function_checks(Node *stmt)
+{
+    AlterFunctionStmt *alterFunctionStmt = (AlterFunctionStmt *)stmt;
+    ListCell *action;
+    DefElem *defel;
+    bool securityDefiner = false;
+    HeapTuple proctup;
+    Oid funcoid;
+    Oid owner;
+    char *owner_name;
+
+    if (!node_type_matches(stmt, T_AlterFunctionStmt, "alter_function_checks"))
+        return;
+
+    foreach (action, alterFunctionStmt->actions)
+    {
+        defel = (DefElem *)lfirst(action);
+        /* SECURITY DEFINER, EXTERNAL SECURITY DEFINER */
+        if (strncmp(defel->defname, "security", 9) == 0)
+            securityDefiner = defGetBoolean(defel);
+    }
+    /* the other actions aren't checked */
+    if (!securityDefiner)
+    {
+        default_deny_checks(stmt);
+        return;
+    }
+
+    funcoid = LookupFuncWithArgs(alterFunctionStmt->objtype, alterFunctionStmt->func, true);
+    if (!OidIsValid(funcoid))
+        return;
+
+    proctup = SearchSysCache1(PROCOID, ObjectIdGetDatum(funcoid));
+    if (!HeapTupleIsValid(proctup))
+        return;
+    owner = ((Form_pg_proc)GETSTRUCT(proctup))->proowner;
+    ReleaseSysCache(proctup);
+
+    owner_name = GetUserNameFromId(owner, true);
+    if (!(owner_name != NULL && allow_superuser_role(owner_name)) && !is_restricted_role(owner))
+        return;
+
+    deny_unless_reserved(utility_statement_name(stmt), "SECURITY DEFINER for function %s of role %s not allowed",
+                         NameListToString(alterFunctionStmt->func->objname), owner_name ? owner_name : "unknown");
+}
+
+/* ALTER FUNCTION ... OWNER TO
+ * handing a function to a superuser, a role holding one of the privileged permissions or
+ * one of the reserved roles makes a SECURITY DEFINER function run with their privileges.
+ * Only allowed for the reserved roles, and nobody in strict mode.
+ */
+static void
+alter_owner_checks(Node *stmt)
+{
+    AlterOwnerStmt *alterOwnerStmt = (AlterOwnerStmt *)stmt;
+    Oid roleoid;
+    char *rolename;
+
+    if (!node_type_matches(stmt, T_AlterOwnerStmt, "alter_owner_checks"))
+        return;
+
+    /* the owner changes of other objects aren't checked */
+    if (alterOwnerStmt->objectType != OBJECT_FUNCTION &&
+        alterOwnerStmt->objectType != OBJECT_PROCEDURE &&
+        alterOwnerStmt->objectType != OBJECT_ROUTINE &&
+        alterOwnerStmt->objectType != OBJECT_AGGREGATE)
+    {
+        default_deny_checks(stmt);
+        return;
+    }
+
+    roleoid = get_rolespec_oid(alterOwnerStmt->newowner, true);
+    rolename = rolespec_display_name(alterOwnerStmt->newowner);
+    if (!allow_superuser_role(rolename) && !(OidIsValid(roleoid) && is_restricted_role(roleoid)))
+        return;
+
+    deny_unless_reserved(utility_statement_name(stmt), "changing the owner of function %s to role %s not allowed",
+                         object_node_name(alterOwnerStmt->object), rolename);
+}
+
+/* CREATE ACCESS METHOD
+ * the handler function runs inside every backend using the access method. Only allowed
+ * for the reserved roles, and nobody in strict mode. A handler in an untrusted language
+ * is denied for everyone. The monitored builtins can't be a handler, postgres requires
+ * the handler to return index_am_handler or table_am_handler.
+ */
+static void
+create_access_method_checks(Node *stmt)
+{
+    CreateAmStmt *createAmStmt = (CreateAmStmt *)stmt;
+    Oid argtype = INTERNALOID;
+    Oid funcoid;
+    HeapTuple proctup;
+    char *language = NULL;
+
+    if (!node_type_matches(stmt, T_CreateAmStmt, "create_access_method_checks"))
+        return;
+
+    if (deny_unless_reserved("CREATE ACCESS METHOD", "CREATE ACCESS METHOD %s not allowed (handler %s)", createAmStmt->amname, NameListToString(createAmStmt->handler_name)))
+        return;
+
+    /* same lookup as CREATE ACCESS METHOD itself, handlers take a single internal argument */
+    funcoid = LookupFuncName(createAmStmt->handler_name, 1, &argtype, true);
+    if (!OidIsValid(funcoid))
+        return;
+
+    proctup = SearchSysCache1(PROCOID, ObjectIdGetDatum(funcoid));
+    if (!HeapTupleIsValid(proctup))
+        return;
+    language = get_language_name(((Form_pg_proc)GETSTRUCT(proctup))->prolang, true);
+    ReleaseSysCache(proctup);
+
+    if (language != NULL && is_untrusted_language(language))
+    {
+        gatekeeper_deny("CREATE ACCESS METHOD", "untrusted_language", "CREATE ACCESS METHOD %s not allowed, handler %s is in LANGUAGE %s",
+                        createAmStmt->amname, NameListToString(createAmStmt->handler_name), language);
+        return;
+    }
+}
+
+static bool
+is_protected_guc(const char *name)
+{
+    int i;
+
+    if (name == NULL)
+        return false;
+
+    // setting names are case insensitive
+    if (pg_strncasecmp(name, "aiven.", strlen("aiven.")) == 0)
+        return true;
+    for (i = 0; i < NUM_PROTECTED_GUCS; i++)
+    {
+        if (pg_strcasecmp(name, protected_guc_names[i]) == 0)
+            return true;
+    }
+    return false;
+}
+
+/* resetting a protected setting is always allowed, it only removes the stored default */
+static void
+protected_guc_set_checks(const char *statement_type, const char *target, VariableSetStmt *setstmt)
+{
+    if (setstmt == NULL || (setstmt->kind != VAR_SET_VALUE && setstmt->kind != VAR_SET_CURRENT))
+        return;
+    if (!is_protected_guc(setstmt->name))
+        return;
+
+    deny_unless_reserved(statement_type, "%s SET %s not allowed (%s)", statement_type, setstmt->name, target);
+}
+
+static void
+alter_database_set_checks(Node *stmt)
+{
+    AlterDatabaseSetStmt *alterDatabaseSetStmt = (AlterDatabaseSetStmt *)stmt;
+
+    if (!node_type_matches(stmt, T_AlterDatabaseSetStmt, "alter_database_set_checks"))
+        return;
+
+    protected_guc_set_checks("ALTER DATABASE", psprintf("database %s", alterDatabaseSetStmt->dbname), alterDatabaseSetStmt->setstmt);
+}
+
+static void
+alter_role_set_checks(Node *stmt)
+{
+    AlterRoleSetStmt *alterRoleSetStmt = (AlterRoleSetStmt *)stmt;
+    const char *target;
+
+    if (!node_type_matches(stmt, T_AlterRoleSetStmt, "alter_role_set_checks"))
+        return;
+
+    // ALTER ROLE ALL SET has no role
+    if (alterRoleSetStmt->role == NULL)
+        target = "all roles";
+    else
+        target = psprintf("role %s", rolespec_display_name(alterRoleSetStmt->role));
+    if (alterRoleSetStmt->database != NULL)
+        target = psprintf("%s in database %s", target, alterRoleSetStmt->database);
+
+    protected_guc_set_checks("ALTER ROLE", target, alterRoleSetStmt->setstmt);
+}
+
+/* the function denylist is the policy of the object access hook, it is only read at server start.
+ * PostgreSQL already refuses to change a PGC_POSTMASTER setting in a session, deny it here as
+ * well so that it doesn't depend on how the setting is defined, not even for the reserved roles.
+ */
+static void
+variable_set_checks(Node *stmt)
+{
+    VariableSetStmt *variableSetStmt = (VariableSetStmt *)stmt;
+
+    if (!node_type_matches(stmt, T_VariableSetStmt, "variable_set_checks"))
+        return;
+
+    if (variableSetStmt->name == NULL || pg_strcasecmp(variableSetStmt->name, "aiven.pg_security_reserved_functions") != 0)
+        return;
+
+    gatekeeper_hard_deny("SET", "locked_setting", "aiven.pg_security_reserved_functions can only be set at server start");
+}
+
+/* an exclusive lock on a system catalog stalls everything that needs it, eg: LOCK pg_authid
+ * IN ACCESS EXCLUSIVE MODE blocks all logins and role management until the transaction ends.
+ * SHARE and stronger modes conflict with the writes to a catalog, the weaker ones only with
+ * other exclusive locks.
+ */
+static void
+lock_checks(Node *stmt)
+{
+    LockStmt *lockStmt = (LockStmt *)stmt;
+    ListCell *cell;
+    RangeVar *relation;
+    Oid relid;
+
+    if (!node_type_matches(stmt, T_LockStmt, "lock_checks"))
+        return;
+
+    if (lockStmt->mode < ShareLock)
+        return;
+
+    foreach (cell, lockStmt->relations)
+    {
+        relation = (RangeVar *)lfirst(cell);
+
+        /* no lock, the statement itself takes it */
+        relid = RangeVarGetRelid(relation, NoLock, true);
+        if (!OidIsValid(relid) || !IsCatalogRelationOid(relid))
+            continue;
+
+        if (deny_unless_reserved("LOCK TABLE", "LOCK TABLE %s (%s) not allowed on a system catalog",
+                                 get_rel_name(relid), GetLockmodeName(DEFAULT_LOCKMETHOD, lockStmt->mode)))
+            return;
+    }
+}
+
+/* ALTER SYSTEM SET of the agent configuration goes through the check hooks of the settings,
+ * which refuse it in strict mode and in a restricted context. RESET, SET ... TO DEFAULT and
+ * RESET ALL only remove the entries from postgresql.auto.conf without checking the value,
+ * refuse them in the same cases and for non-reserved roles.
+ */
+static void
+alter_system_checks(Node *stmt)
+{
+    AlterSystemStmt *alterSystemStmt = (AlterSystemStmt *)stmt;
+    VariableSetStmt *setstmt = alterSystemStmt->setstmt;
+    const char *reason;
+
+    if (!node_type_matches(stmt, T_AlterSystemStmt, "alter_system_checks"))
+        return;
+
+    if (setstmt == NULL)
+        return;
+    if (setstmt->kind == VAR_SET_VALUE || setstmt->kind == VAR_SET_CURRENT)
+        return;
+    if (setstmt->kind != VAR_RESET_ALL &&
+        (setstmt->name == NULL || pg_strncasecmp(setstmt->name, "aiven.", strlen("aiven.")) != 0))
+        return;
+
+    /* same as allowed_guc_change_check_hook, and outside of those contexts only the reserved
+     * roles can remove the persisted values, there is no new value for a check hook to refuse
+     */
+    if (pg_security_agent_strict || creating_extension || is_security_restricted() || is_elevated())
+        reason = pg_security_agent_strict ? "strict_mode" : restricted_context_reason();
+    else if (!is_reserved_caller())
+        reason = "caller_not_reserved";
+    else
+        return;
+
+    if (setstmt->kind == VAR_RESET_ALL)
+        gatekeeper_deny("ALTER SYSTEM", reason, "ALTER SYSTEM RESET ALL not allowed, it resets the aiven gatekeeper configuration");
+    else
+        gatekeeper_deny("ALTER SYSTEM", reason, "ALTER SYSTEM RESET %s not allowed", setstmt->name);
+}
+
+/* resolve an extension against aiven.pg_security_extension_policy.
+ * name allows and !name denies that extension, * allows and !* denies all others.
+ * An entry for the extension itself takes precedence over a wildcard and for the same
+ * name the negation wins, the order of the entries doesn't matter. An empty policy allows all.
+ */
+static bool
+extension_policy_allows(const char *policy, const char *extname)
+{
+    List *elemlist;
+    ListCell *cell;
+    bool allowed = false;
+    bool denied = false;
+    bool allow_all = false;
+    bool deny_all = false;
+
+    split_identifier_list(policy, &elemlist, NULL);
+    if (elemlist == NIL)
+        return true;
+
+    foreach (cell, elemlist)
+    {
+        char *entry = (char *)lfirst(cell);
+
+        if (strcmp(entry, "*") == 0)
+            allow_all = true;
+        else if (strcmp(entry, "!*") == 0)
+            deny_all = true;
+        else if (entry[0] == '!' && strcmp(entry + 1, extname) == 0)
+            denied = true;
+        else if (strcmp(entry, extname) == 0)
+            allowed = true;
+    }
+    list_free(elemlist);
+
+    if (denied)
+        return false;
+    if (allowed)
+        return true;
+    return allow_all && !deny_all;
+}
+
+/* CREATE EXTENSION */
+static void
+create_extension_checks(Node *stmt)
+{
+    /* block file_fdw extension. Case sensitive compare is ok, since the extension name is lower case when read from extname*/
+    CreateExtensionStmt *createExtStmt = (CreateExtensionStmt *)stmt;
+
+    if (!node_type_matches(stmt, T_CreateExtensionStmt, "create_extension_checks"))
+        return;
+
+    if (strncmp(createExtStmt->extname, "file_fdw", 9) == 0)
+    {
+        if (reserved_bypass_allowed("extension"))
+            gatekeeper_audit_log(LOG, "CREATE EXTENSION", "reserved_bypass", "allow", "file_fdw extension allowed for reserved role");
+        else
+            gatekeeper_deny("CREATE EXTENSION", "always", "file_fdw extension not allowed");
+    }
+
+    /* the reserved roles aren't limited by the policy, except in strict mode */
+    if (!extension_policy_allows(extension_policy, createExtStmt->extname) &&
+        (is_strict_mode_enabled() || !is_reserved_caller()))
+    {
+        gatekeeper_deny("CREATE EXTENSION", "extension_policy", "extension %s not allowed by aiven.pg_security_extension_policy", createExtStmt->extname);
+        return;
+    }
+
+    privileged_statement_checks("CREATE EXTENSION", psprintf("installing extension %s", createExtStmt->extname));
+}
+
+/* check if the foreign server uses a wrapper that gives access to the server filesystem.
+ * The wrapper can have any name, so also look at its handler function.
+ */
+static bool
+restricted_foreign_server(const char *servername)
+{
+    ForeignServer *server;
+    ForeignDataWrapper *fdw;
+    char *handler_name;
+
+    server = GetForeignServerByName(servername, true);
+    if (server == NULL)
+        return false;
+
+    fdw = GetForeignDataWrapper(server->fdwid);
+    if (strcmp(fdw->fdwname, "file_fdw") == 0)
+        return true;
+    if (!OidIsValid(fdw->fdwhandler))
+        return false;
+
+    handler_name = get_func_name(fdw->fdwhandler);
+    return handler_name != NULL && strcmp(handler_name, "file_fdw_handler") == 0;
+}
+
+/* CREATE FOREIGN TABLE
+ * a server on file_fdw can only exist when a reserved role created it, but then
+ * anyone with USAGE on it could read server files through a foreign table
+ */
+static void
+create_foreign_table_checks(Node *stmt)
+{
+    CreateForeignTableStmt *createForeignTableStmt = (CreateForeignTableStmt *)stmt;
+
+    if (!node_type_matches(stmt, T_CreateForeignTableStmt, "create_foreign_table_checks"))
+        return;
+
+    if (!restricted_foreign_server(createForeignTableStmt->servername))
+        return;
+
+    deny_unless_reserved("CREATE FOREIGN TABLE", "foreign table on server %s not allowed (table %s)",
+                         createForeignTableStmt->servername, createForeignTableStmt->base.relation->relname);
+}
+
+/* IMPORT FOREIGN SCHEMA creates the foreign tables in bulk, same rules as CREATE FOREIGN TABLE */
+static void
+import_foreign_schema_checks(Node *stmt)
+{
+    ImportForeignSchemaStmt *importStmt = (ImportForeignSchemaStmt *)stmt;
+
+    if (!node_type_matches(stmt, T_ImportForeignSchemaStmt, "import_foreign_schema_checks"))
+        return;
+
+    if (!restricted_foreign_server(importStmt->server_name))
+        return;
+
+    deny_unless_reserved("IMPORT FOREIGN SCHEMA", "importing foreign tables from server %s not allowed (schema %s)",
+                         importStmt->server_name, importStmt->local_schema);
+}
+
+/* TRUNCATE of tables in the protected schemas is only allowed for reserved roles,
+ * and not at all in strict mode
+ */
+static void
+truncate_checks(Node *stmt)
+{
+    TruncateStmt *truncateStmt = (TruncateStmt *)stmt;
+    ListCell *relationCell;
+    RangeVar *relation;
+    char *schema_name;
+
+    if (!node_type_matches(stmt, T_TruncateStmt, "truncate_checks"))
+        return;
+
+    foreach (relationCell, truncateStmt->relations)
+    {
+        relation = (RangeVar *)lfirst(relationCell);
+        schema_name = protected_relation_schema(relation);
+        if (schema_name == NULL)
+            continue;
+
+        if (deny_unless_reserved("TRUNCATE", "TRUNCATE of protected table %s.%s not allowed", schema_name, relation->relname))
+            return;
+    }
+}
+
+/* ALTER DEFAULT PRIVILEGES */
+static void
+alter_default_privileges_checks(Node *stmt)
+{
+    AlterDefaultPrivilegesStmt *alterDefPrivStmt = (AlterDefaultPrivilegesStmt *)stmt;
+    ListCell *granteeCell;
+    RoleSpec *grantee;
+
+    if (!node_type_matches(stmt, T_AlterDefaultPrivilegesStmt, "alter_default_privileges_checks"))
+        return;
+
+    /* only a GRANT can widen access, REVOKE is always fine */
+    if (!alterDefPrivStmt->action->is_grant)
+        return;
+
+    /* granting to PUBLIC silently opens up every future object created by the target roles,
+     * deny it in strict mode and otherwise leave a trail in the server log.
+     */
+    foreach (granteeCell, alterDefPrivStmt->action->grantees)
+    {
+        grantee = (RoleSpec *)lfirst(granteeCell);
+        if (grantee->roletype != ROLESPEC_PUBLIC)
+            continue;
+
+        if (is_strict_mode_enabled())
+        {
+            gatekeeper_deny("ALTER DEFAULT PRIVILEGES", "strict_mode", "ALTER DEFAULT PRIVILEGES granting to PUBLIC not allowed");
+            return;
+        }
+        gatekeeper_audit_log(LOG, "ALTER DEFAULT PRIVILEGES", "grant_to_public", "allow", "ALTER DEFAULT PRIVILEGES granting to PUBLIC");
+    }
+}
+
+/* CREATE EVENT TRIGGER
+ * event triggers fire for every matching DDL in the database with the privileges of
+ * the function owner, which makes them a way to run code around our checks.
+ * Only reserved roles can create them, nobody in strict mode.
+ */
+static void
+create_event_trigger_checks(Node *stmt)
+{
+    CreateEventTrigStmt *createEventTrigStmt = (CreateEventTrigStmt *)stmt;
+
+    if (!node_type_matches(stmt, T_CreateEventTrigStmt, "create_event_trigger_checks"))
+        return;
+
+    deny_unless_reserved("CREATE EVENT TRIGGER", "CREATE EVENT TRIGGER %s not allowed (function %s)", createEventTrigStmt->trigname, NameListToString(createEventTrigStmt->funcname));
+}
+
+/* CREATE SCHEMA ... AUTHORIZATION
+ * the schema and every object created in the same statement end up owned by the
+ * authorization role. Handing those to a superuser or reserved role is only allowed
+ * for reserved roles, and never allowed towards a superuser in strict mode.
+ */
+static void
+create_schema_checks(Node *stmt)
+{
+    CreateSchemaStmt *createSchemaStmt = (CreateSchemaStmt *)stmt;
+    Oid roleoid;
+    char *rolename;
+    char *schemaname;
+
+    if (!node_type_matches(stmt, T_CreateSchemaStmt, "create_schema_checks"))
+        return;
+
+    if (createSchemaStmt->authrole == NULL)
+        return;
+
+    roleoid = get_rolespec_oid(createSchemaStmt->authrole, true);
+    rolename = rolespec_display_name(createSchemaStmt->authrole);
+    /* without a schema name the schema is named after the role */
+    schemaname = createSchemaStmt->schemaname ? createSchemaStmt->schemaname : rolename;
+
+    if (is_strict_mode_enabled() && OidIsValid(roleoid) && superuser_arg(roleoid))
+    {
+        gatekeeper_deny("CREATE SCHEMA", "strict_mode", "CREATE SCHEMA %s AUTHORIZATION %s not allowed", schemaname, rolename);
+        return;
+    }
+
+    /* creating a schema for yourself doesn't give anything away */
+    if (!OidIsValid(roleoid) || roleoid == GetUserId())
+        return;
+
+    if ((superuser_arg(roleoid) || allow_superuser_role(rolename)) && !is_reserved_caller())
+    {
+        gatekeeper_deny("CREATE SCHEMA", "caller_not_reserved", "CREATE SCHEMA %s AUTHORIZATION %s not allowed", schemaname, rolename);
+        return;
+    }
+}
+
+/* ALTER TABLE
+ * changing row level security on tables in the protected schemas is only
+ * allowed for reserved roles, and not at all in strict mode
+ */
+static void
+alter_table_checks(Node *stmt)
+{
+    AlterTableStmt *alterTableStmt = (AlterTableStmt *)stmt;
+    ListCell *cmdCell;
+    AlterTableCmd *cmd;
+    const char *action;
+    char *schema_name = NULL;
+    bool schema_resolved = false;
+
+    if (!node_type_matches(stmt, T_AlterTableStmt, "alter_table_checks"))
+        return;
+
+    foreach (cmdCell, alterTableStmt->cmds)
+    {
+        cmd = (AlterTableCmd *)lfirst(cmdCell);
+        switch (cmd->subtype)
+        {
+        case AT_EnableRowSecurity:
+            action = "ENABLE ROW LEVEL SECURITY";
+            break;
+        case AT_DisableRowSecurity:
+            action = "DISABLE ROW LEVEL SECURITY";
+            break;
+        case AT_ForceRowSecurity:
+            action = "FORCE ROW LEVEL SECURITY";
+            break;
+        case AT_NoForceRowSecurity:
+            action = "NO FORCE ROW LEVEL SECURITY";
+            break;
+        default:
+            continue;
+        }
+
+        /* only look up the relation once there is a subcommand we care about */
+        if (!schema_resolved)
+        {
+            schema_name = protected_relation_schema(alterTableStmt->relation);
+            schema_resolved = true;
+        }
+        if (schema_name == NULL)
+            return;
+
+        if (deny_unless_reserved("ALTER TABLE", "ALTER TABLE %s.%s %s not allowed on protected table", schema_name, alterTableStmt->relation->relname, action))
+            return;
+    }
+}
+
+/* CLUSTER and VACUUM FULL rewrite the whole table under an ACCESS EXCLUSIVE lock,
+ * on the catalogs this blocks every new connection until it is done.
+ * Only reserved roles can do this on tables in the protected schemas, and it is logged.
+ */
+static void
+rewrite_protected_table_checks(const char *statement_type, RangeVar *relation)
+{
+    char *schema_name;
+
+    schema_name = protected_relation_schema(relation);
+    if (schema_name == NULL)
+        return;
+
+    if (deny_unless_reserved(statement_type, "%s of protected table %s.%s not allowed", statement_type, schema_name, relation->relname))
+        return;
+    gatekeeper_audit_log(LOG, statement_type, "protected_table", "allow",
+                         psprintf("%s of protected table %s.%s", statement_type, schema_name, relation->relname));
+}
+
+/* without a table every table the role can process is rewritten, for a superuser that
+ * includes the catalogs. Same rules as naming one of them, when a protected schema exists.
+ */
+static void
+rewrite_protected_database_checks(const char *statement_type)
+{
+    List *elemlist;
+    ListCell *cell;
+    char *schema_name = NULL;
+
+    split_identifier_list(protected_schemas, &elemlist, NULL);
+    foreach (cell, elemlist)
+    {
+        if (OidIsValid(get_namespace_oid((char *)lfirst(cell), true)))
+        {
+            schema_name = (char *)lfirst(cell);
+            break;
+        }
+    }
+    list_free(elemlist);
+    if (schema_name == NULL)
+        return;
+
+    if (deny_unless_reserved(statement_type, "%s without a table not allowed, it rewrites the tables of protected schema %s", statement_type, schema_name))
+        return;
+    gatekeeper_audit_log(LOG, statement_type, "protected_table", "allow",
+                         psprintf("%s of all tables, including protected schema %s", statement_type, schema_name));
+}
+
+/* CLUSTER */
+static void
+cluster_checks(Node *stmt)
+{
+    ClusterStmt *clusterStmt = (ClusterStmt *)stmt;
+
+    if (!node_type_matches(stmt, T_ClusterStmt, "cluster_checks"))
+        return;
+
+    /* without a table only the tables clustered before are reclustered, and only
+     * the ones owned by the current role. Leave that to the postgres checks.
+     */
+    if (clusterStmt->relation == NULL)
+        return;
+
+    rewrite_protected_table_checks("CLUSTER", clusterStmt->relation);
+}
+
+/* VACUUM FULL */
+static void
+vacuum_checks(Node *stmt)
+{
+    VacuumStmt *vacuumStmt = (VacuumStmt *)stmt;
+    ListCell *cell;
+    VacuumRelation *vacuumRelation;
+    bool full = false;
+#if PG12_GTE
+    DefElem *defel;
+#endif
+
+    if (!node_type_matches(stmt, T_VacuumStmt, "vacuum_checks"))
+        return;
+
+#if PG12_GTE
+    /* only VACUUM has the FULL option, ANALYZE uses the same statement */
+    if (!vacuumStmt->is_vacuumcmd)
+        return;
+
+    foreach (cell, vacuumStmt->options)
+    {
+        defel = (DefElem *)lfirst(cell);
+        if (strncmp(defel->defname, "full", 5) == 0)
+            full = defGetBoolean(defel);
+    }
+#else
+    full = (vacuumStmt->options & VACOPT_FULL) != 0;
+#endif
+    if (!full)
+        return;
+
+    /* without tables every table in the database is processed, postgres only skips the
+     * ones the current role doesn't own, nothing for a superuser
+     */
+    if (vacuumStmt->rels == NIL)
+    {
+        rewrite_protected_database_checks("VACUUM FULL");
+        return;
+    }
+    foreach (cell, vacuumStmt->rels)
+    {
+        vacuumRelation = (VacuumRelation *)lfirst(cell);
+        if (vacuumRelation->relation != NULL)
+            rewrite_protected_table_checks("VACUUM FULL", vacuumRelation->relation);
+    }
+}
+
+/* REASSIGN OWNED and DROP OWNED on a superuser, privileged or reserved role move or drop
+ * the objects the service depends on. Only reserved roles can do this, nobody in strict mode.
+ */
+static void
+owned_role_checks(const char *statement_type, RoleSpec *role)
+{
+    Oid roleoid;
+    char *rolename;
+
+    roleoid = get_rolespec_oid(role, true);
+    if (!OidIsValid(roleoid))
+        return;
+    rolename = rolespec_display_name(role);
+    if (!is_restricted_role(roleoid) && !allow_superuser_role(rolename))
+        return;
+
+    deny_unless_reserved(statement_type, "%s involving reserved role %s not allowed", statement_type, rolename);
+}
+
+/* REASSIGN OWNED BY ... TO ... */
+static void
+reassign_owned_checks(Node *stmt)
+{
+    ReassignOwnedStmt *reassignStmt = (ReassignOwnedStmt *)stmt;
+    ListCell *roleCell;
+
+    if (!node_type_matches(stmt, T_ReassignOwnedStmt, "reassign_owned_checks"))
+        return;
+
+    foreach (roleCell, reassignStmt->roles)
+        owned_role_checks("REASSIGN OWNED", (RoleSpec *)lfirst(roleCell));
+    owned_role_checks("REASSIGN OWNED", reassignStmt->newrole);
+}
+
+/* DROP OWNED BY ... */
+static void
+drop_owned_checks(Node *stmt)
+{
+    DropOwnedStmt *dropOwnedStmt = (DropOwnedStmt *)stmt;
+    ListCell *roleCell;
+
+    if (!node_type_matches(stmt, T_DropOwnedStmt, "drop_owned_checks"))
+        return;
+
+    foreach (roleCell, dropOwnedStmt->roles)
+        owned_role_checks("DROP OWNED", (RoleSpec *)lfirst(roleCell));
+}
+
+/* the schema the aiven_gatekeeper extension is installed in, InvalidOid when it isn't installed */
+static Oid
+gatekeeper_extension_schema(void)
+{
+    Relation rel;
+    SysScanDesc scan;
+    HeapTuple tuple;
+    Form_pg_extension extension;
+    Oid schema = InvalidOid;
+
+    rel = table_open(ExtensionRelationId, AccessShareLock);
+    scan = systable_beginscan(rel, InvalidOid, false, NULL, 0, NULL);
+    while (HeapTupleIsValid(tuple = systable_getnext(scan)))
+    {
+        extension = (Form_pg_extension)GETSTRUCT(tuple);
+        if (strcmp(NameStr(extension->extname), "aiven_gatekeeper") == 0)
+        {
+            schema = extension->extnamespace;
+            break;
+        }
+    }
+    systable_endscan(scan);
+    table_close(rel, AccessShareLock);
+
+    return schema;
+}
+
+static void
+extension_drop_checks(const char *statement_type, const char *description, const char *audit_message)
+{
+    if (!deny_unless_reserved(statement_type, "%s is not allowed", description))
+        gatekeeper_audit_log(WARNING, statement_type, "self_protection", "allow", audit_message);
+}
+
+/* DROP EXTENSION aiven_gatekeeper, DROP SCHEMA ... CASCADE of its schema
+ * the preloaded library keeps running, but the helper functions operators rely on
+ * are gone. Only the reserved roles can drop it, nobody in strict mode, and an
+ * allowed drop is still logged as a warning. The extension is relocatable, so the
+ * schema is looked up when it is dropped. Other drops are unchecked statements
+ * for the default deny.
+ */
+static void
+drop_stmt_checks(Node *stmt)
+{
+    DropStmt *dropStmt = (DropStmt *)stmt;
+    ListCell *object;
+    Oid schema;
+    char *schema_name;
+
+    if (!node_type_matches(stmt, T_DropStmt, "drop_stmt_checks"))
+        return;
+
+    if (dropStmt->removeType == OBJECT_EXTENSION)
+    {
+        foreach (object, dropStmt->objects)
+        {
+            if (strcmp(strVal(lfirst(object)), "aiven_gatekeeper") != 0)
+                continue;
+
+            extension_drop_checks("DROP EXTENSION", "dropping the aiven_gatekeeper extension",
+                                  "aiven_gatekeeper extension dropped by reserved role");
+            return;
+        }
+    }
+
+    /* without CASCADE postgres refuses to drop a schema with the extension in it */
+    if (dropStmt->removeType == OBJECT_SCHEMA && dropStmt->behavior == DROP_CASCADE)
+    {
+        schema = gatekeeper_extension_schema();
+        foreach (object, dropStmt->objects)
+        {
+            schema_name = strVal(lfirst(object));
+            if (!OidIsValid(schema) || get_namespace_oid(schema_name, true) != schema)
+                continue;
+
+            extension_drop_checks("DROP SCHEMA", psprintf("dropping schema %s with the aiven_gatekeeper extension", schema_name),
+                                  psprintf("aiven_gatekeeper extension dropped with schema %s by reserved role", schema_name));
+            return;
+        }
+    }
+
+    default_deny_checks(stmt);
+}
+
+/* the object of a SECURITY LABEL or ALTER ... OWNER statement is a name list,
+ * a plain name, a type name or a function signature depending on the object type
+ */
+static char *
+object_node_name(Node *object)
+{
+    if (object == NULL)
+        return "[unknown]";
+    if (IsA(object, List))
+        return NameListToString((List *)object);
+    if (IsA(object, String))
+        return strVal(object);
+    if (IsA(object, TypeName))
+        return TypeNameToString((TypeName *)object);
+    if (IsA(object, ObjectWithArgs))
+        return NameListToString(((ObjectWithArgs *)object)->objname);
+    return "[unknown]";
+}
+
+/* SECURITY LABEL
+ * labels are the policy input of label based security providers such as sepgsql,
+ * changing them could loosen a policy layered on top of ours. Only reserved roles
+ * are allowed to set or remove a label, and nobody in strict mode.
+ */
+static void
+security_label_checks(Node *stmt)
+{
+    SecLabelStmt *secLabelStmt = (SecLabelStmt *)stmt;
+    const char *object_name;
+    const char *provider;
+
+    if (!node_type_matches(stmt, T_SecLabelStmt, "security_label_checks"))
+        return;
+
+    object_name = object_node_name(secLabelStmt->object);
+    /* without a provider the only loaded provider is used */
+    provider = secLabelStmt->provider ? secLabelStmt->provider : "[default]";
+
+    deny_unless_reserved("SECURITY LABEL", "SECURITY LABEL on %s not allowed (provider %s)", object_name, provider);
+}
+
+/* register an additional check for a utility statement type.
+ * Checks for the same statement type run in registration order, the default
+ * checks are registered first in _PG_init. Meant to be called from the _PG_init
+ * of a library loaded after this one in shared_preload_libraries.
+ */
+void
+aiven_gatekeeper_register_check(NodeTag tag, gatekeeper_check_fn check)
+{
+    register_named_check(tag, NULL, check);
+}
+
+/* the default checks also keep the name of their statement type, taken from the tag */
+#define REGISTER_DEFAULT_CHECK(type, check) register_named_check(T_##type, #type, check)
+
+static void
+register_named_check(NodeTag tag, const char *name, gatekeeper_check_fn check)
+{
+    if (check == NULL)
+        elog(ERROR, "aiven_gatekeeper: check function must not be NULL");
+    if (num_utility_checks >= GATEKEEPER_MAX_CHECKS)
+        elog(ERROR, "aiven_gatekeeper: too many registered checks (max %d)", GATEKEEPER_MAX_CHECKS);
+
+    utility_checks[num_utility_checks].tag = tag;
+    utility_checks[num_utility_checks].name = name;
+    utility_checks[num_utility_checks].check = check;
+    num_utility_checks++;
+}
+
+static void
+register_default_checks(void)
+{
+    REGISTER_DEFAULT_CHECK(AlterRoleStmt, alter_role_checks);
+    REGISTER_DEFAULT_CHECK(CreateRoleStmt, create_role_checks);
+    REGISTER_DEFAULT_CHECK(GrantRoleStmt, grant_role_checks);
+    REGISTER_DEFAULT_CHECK(RenameStmt, rename_role_checks);
+    REGISTER_DEFAULT_CHECK(CopyStmt, copy_stmt_checks);
+    REGISTER_DEFAULT_CHECK(CreateFunctionStmt, create_function_checks);
+    REGISTER_DEFAULT_CHECK(DoStmt, do_stmt_checks);
+    REGISTER_DEFAULT_CHECK(CreateTrigStmt, create_trigger_checks);
+    REGISTER_DEFAULT_CHECK(AlterFunctionStmt, alter_function_checks);
+    REGISTER_DEFAULT_CHECK(AlterOwnerStmt, alter_owner_checks);
+    REGISTER_DEFAULT_CHECK(CreateAmStmt, create_access_method_checks);
+    REGISTER_DEFAULT_CHECK(AlterDatabaseSetStmt, alter_database_set_checks);
+    REGISTER_DEFAULT_CHECK(AlterRoleSetStmt, alter_role_set_checks);
+    REGISTER_DEFAULT_CHECK(VariableSetStmt, variable_set_checks);
+    REGISTER_DEFAULT_CHECK(LockStmt, lock_checks);
+    REGISTER_DEFAULT_CHECK(AlterSystemStmt, alter_system_checks);
+    REGISTER_DEFAULT_CHECK(CreateExtensionStmt, create_extension_checks);
+    REGISTER_DEFAULT_CHECK(CreateForeignTableStmt, create_foreign_table_checks);
+    REGISTER_DEFAULT_CHECK(ImportForeignSchemaStmt, import_foreign_schema_checks);
+    REGISTER_DEFAULT_CHECK(TruncateStmt, truncate_checks);
+    REGISTER_DEFAULT_CHECK(AlterDefaultPrivilegesStmt, alter_default_privileges_checks);
+    REGISTER_DEFAULT_CHECK(CreateEventTrigStmt, create_event_trigger_checks);
+    REGISTER_DEFAULT_CHECK(CreateSchemaStmt, create_schema_checks);
+    REGISTER_DEFAULT_CHECK(AlterTableStmt, alter_table_checks);
+    REGISTER_DEFAULT_CHECK(ClusterStmt, cluster_checks);
+    REGISTER_DEFAULT_CHECK(VacuumStmt, vacuum_checks);
+    REGISTER_DEFAULT_CHECK(ReassignOwnedStmt, reassign_owned_checks);
+    REGISTER_DEFAULT_CHECK(DropOwnedStmt, drop_owned_checks);
+    REGISTER_DEFAULT_CHECK(DropStmt, drop_stmt_checks);
+    REGISTER_DEFAULT_CHECK(SecLabelStmt, security_label_checks);
+
+    /* statement types deliberately without a check:
+     *
+     * DROP ROLE - dropping from an elevated context should eventually be a check for
+     * dropping reserved roles.
+     *
+     * PREPARE/EXECUTE - COPY can't be smuggled through a prepared statement, the grammar
+     * only accepts SELECT/INSERT/UPDATE/DELETE/MERGE and VALUES after PREPARE ... AS
+     * and EXECUTE runs the prepared plan through the executor.
+     * COPY issued dynamically (plpgsql EXECUTE, SPI) still reaches ProcessUtility
+     * as a T_CopyStmt and is inspected by copy_stmt_checks.
+     *
+     * All of these are denied for non-reserved roles with aiven.pg_security_default_deny.
+     *
+     * SET - SET SESSION_AUTHORIZATION would allow bypassing of our dumb privilege escalation check.
+     * even though this should be blocked in extension installation, due to
+     *  ERROR:  cannot set parameter "session_authorization" within security-definer function
+     * so don't do anything.
+     *
+     * A check that only inspects some forms of its statement type hands the other forms
+     * to default_deny_checks: renames other than of a role, ALTER FUNCTION without
+     * SECURITY DEFINER, owner changes of objects other than functions and drops other
+     * than of this extension or its schema. Every other registered check covers its whole statement
+     * type, what passes it is deliberately allowed even with default deny, eg: LOCK
+     * TABLE in a weak mode, TRUNCATE or ALTER TABLE outside of the protected schemas.
+     */
+}
+
+static const char *
+utility_statement_name(Node *stmt)
+{
+#if PG13_GTE
+    return GetCommandTagName(CreateCommandTag(stmt));
+#else
+    return CreateCommandTag(stmt);
+#endif
+}
+
+/* utility statement types without a registered check are allowed by default.
+ * With aiven.pg_security_default_deny they are logged, or denied for
+ * non-reserved roles, turning the registered checks into an allowlist.
+ */
+static void
+default_deny_checks(Node *stmt)
+{
+    const char *statement_name;
+    int i;
+
+    if (default_deny == DEFAULT_DENY_OFF)
+        return;
+
+    for (i = 0; i < NUM_DEFAULT_ALLOWED_STATEMENTS; i++)
+    {
+        if (default_allowed_statements[i] == nodeTag(stmt))
+            return;
+    }
+
+    statement_name = utility_statement_name(stmt);
+    if (default_deny == DEFAULT_DENY_LOG)
+    {
+        gatekeeper_audit_log(LOG, statement_name, "unchecked_statement", "allow", psprintf("%s has no gatekeeper check", statement_name));
+        return;
+    }
+    if (!is_reserved_caller())
+    {
+        gatekeeper_deny(statement_name, "default_deny", "%s not allowed, statement type has no gatekeeper check", statement_name);
+        return;
+    }
+}
+
+/* run every check registered for this statement type.
+ * The checks are picked by the node tag, a check only ever sees the statement
+ * type it was registered for, which is what makes the cast in each check safe.
+ */
+static void
+run_utility_checks(Node *stmt)
+{
+    int i;
+    bool checked = false;
+
+#if GATEKEEPER_MONITOR_ONLY
+    for (i = 0; i < num_utility_checks; i++)
+    {
+        if (utility_checks[i].tag == nodeTag(stmt))
+        {
+            utility_checks[i].check(stmt);
+            checked = true;
+        }
+    }
+    if (!checked)
+        default_deny_checks(stmt);
+#else
+    /* the checks already continue after a denial for the monitor only builds,
+     * collect the denials and abort once with all of them
+     */
+    collecting_denials = true;
+    collected_denials = NIL;
+    PG_TRY();
+    {
+        for (i = 0; i < num_utility_checks; i++)
+        {
+            if (utility_checks[i].tag == nodeTag(stmt))
+            {
+                utility_checks[i].check(stmt);
+                checked = true;
+            }
+        }
+        if (!checked)
+            default_deny_checks(stmt);
+    }
+    PG_CATCH();
+    {
+        /* the hooks outside of the utility checks have to deny right away */
+        collecting_denials = false;
+        collected_denials = NIL;
+        PG_RE_THROW();
+    }
+    PG_END_TRY();
+    collecting_denials = false;
+
+    raise_collected_denials();
+#endif
+}
+
+/* a hook another library replaced without calling it silently disables its checks */
+static void
+report_hook_miss(const char *hook, const char *checks)
+{
+    ereport(WARNING,
+            (errmsg("aiven_gatekeeper %s hook is no longer called, %s depend on the library replacing it", hook, checks),
+             errhint("Load aiven_gatekeeper last in shared_preload_libraries, aiven_gatekeeper_hook_chain() shows the current state.")));
+}
+
+/* after a utility statement completed, check the hooks it must have gone through.
+ * The utility hook itself is checked by the object access hook.
+ */
+static void
+verify_hook_chain(Node *stmt)
+{
+#if GATEKEEPER_EXECUTOR_HOOK
+    bool runs_executor = false;
+#endif
+#if GATEKEEPER_OBJECT_ACCESS_HOOK
+    bool creates_object = false;
+#endif
+
+#if GATEKEEPER_EXECUTOR_HOOK
+    /* statements that always start the executor for their query */
+    if (IsA(stmt, CopyStmt))
+        runs_executor = ((CopyStmt *)stmt)->query != NULL;
+    else if (IsA(stmt, ExecuteStmt))
+        runs_executor = true;
+    else if (IsA(stmt, CreateTableAsStmt))
+        runs_executor = !((CreateTableAsStmt *)stmt)->into->skipData && !((CreateTableAsStmt *)stmt)->if_not_exists;
+
+    if (runs_executor && !executor_hook_called)
+    {
+        executor_hook_called = true;
+        report_hook_miss("ExecutorStart", "executor checks");
+    }
+#endif
+#if GATEKEEPER_OBJECT_ACCESS_HOOK
+    /* creating the object runs the post create object access hook, not when it already existed */
+    if (IsA(stmt, CreateStmt))
+        creates_object = !((CreateStmt *)stmt)->if_not_exists;
+    else if (IsA(stmt, CreateFunctionStmt))
+        creates_object = !((CreateFunctionStmt *)stmt)->replace;
+    else if (IsA(stmt, CreateSchemaStmt))
+        creates_object = !((CreateSchemaStmt *)stmt)->if_not_exists;
+
+    if (creates_object && !oa_hook_called)
+    {
+        oa_hook_called = true;
+        report_hook_miss("object access", "function execute checks");
+    }
+#endif
+}
+
+static void
+gatekeeper_checks(PROCESS_UTILITY_PARAMS)
+{
+    Node *stmt;
+    ProcessUtilityContext saved_context = current_utility_context;
+
+    utility_hook_called = true;
+
+    /* if the agent is disabled, skip all checks */
+    if (!pg_security_agent_enabled)
+    {
+        if (notice_when_disabled && !disabled_notice_sent)
+        {
+            ereport(NOTICE,
+                    (errmsg("aiven gatekeeper security checks are disabled"),
+                     errhint("aiven.pg_security_agent is off.")));
+            disabled_notice_sent = true;
+        }
+
+        /* execute the actual query */
+        run_previous_utility(PROCESS_UTILITY_ARGS);
+
+        /* we are done executing, exit the function */
+        return;
+    }
+
+    /* nothing to check without a statement, don't dereference it here and
+     * leave it to the next hook or postgres itself
+     */
+    if (pstmt == NULL || pstmt->utilityStmt == NULL)
+    {
+        run_previous_utility(PROCESS_UTILITY_ARGS);
+        return;
+    }
+
+    /* get the utilty statment from the planner
+     * https://github.com/postgres/postgres/blob/24d2b2680a8d0e01b30ce8a41c4eb3b47aca5031/src/backend/tcop/utility.c#L575
+     */
+    stmt = pstmt->utilityStmt;
+    if (context != PROCESS_UTILITY_SUBCOMMAND)
+        current_utility_context = context;
+
+    PG_TRY();
+    {
+        if (is_bypass_role())
+            elog(DEBUG1, "aiven_gatekeeper: checks skipped for bypass role %s (%s)",
+                 GetUserNameFromId(GetUserId(), false), utility_statement_name(stmt));
+        else
+            run_utility_checks(stmt);
+    }
+    PG_CATCH();
+    {
+        current_utility_context = saved_context;
+        PG_RE_THROW();
+    }
+    PG_END_TRY();
+    current_utility_context = saved_context;
+
+    /* execute the actual query */
+    run_previous_utility(PROCESS_UTILITY_ARGS);
+
+    verify_hook_chain(stmt);
+}
+
+/* run the statement with the next hook or postgres itself */
+static void
+run_previous_utility(PROCESS_UTILITY_PARAMS)
+{
+    utility_hook_depth++;
+    PG_TRY();
+    {
+        if (prev_ProcessUtility)
+            prev_ProcessUtility(PROCESS_UTILITY_ARGS);
+        else
+            standard_ProcessUtility(PROCESS_UTILITY_ARGS);
+    }
+    PG_CATCH();
+    {
+        utility_hook_depth--;
+        PG_RE_THROW();
+    }
+    PG_END_TRY();
+    utility_hook_depth--;
+}
+
+/* straight copy from fmgr.c
+ * this function isn't exported by fmgr.c, so just
+ * recreate it here
+ */
+static const FmgrBuiltin *
+fmgr_lookupByName(const char *name)
+{
+    int i;
+
+    for (i = 0; i < fmgr_nbuiltins; i++)
+    {
+        // switched to strncmp, 28 is the current max size that can be expected for name, as from reserved_func_names
+        if (strncmp(name, fmgr_builtins[i].funcName, 28) == 0)
+            return fmgr_builtins + i;
+    }
+    return NULL;
+}
+
+static bool
+set_reserved_oids()
+{
+    /* sets the min and max_reserved_oid variables, allowing for skipping builtins search
+     * if an oid is clearly not going to be in the builtins.
+     */
+    const FmgrBuiltin *builtin;
+    List *elemlist;
+    ListCell *cell;
+    int i;
+
+    /* the check hook already refused a list that can't be parsed */
+    if (!split_identifier_list(extra_reserved_functions, &elemlist, NULL))
+        elemlist = NIL;
+    num_reserved_funcs = NUM_RESERVED_FUNCS + list_length(elemlist);
+
+    reserved_func_list = (const char **)malloc(num_reserved_funcs * sizeof(char *));
+    reserved_func_oids = (Oid *)malloc(num_reserved_funcs * sizeof(Oid));
+    if (reserved_func_list == NULL || reserved_func_oids == NULL)
+    {
+        return false;
+    }
+    for (i = 0; i < NUM_RESERVED_FUNCS; i++)
+        reserved_func_list[i] = reserved_func_names[i];
+    foreach (cell, elemlist)
+    {
+        reserved_func_list[i] = strdup((char *)lfirst(cell));
+        if (reserved_func_list[i] == NULL)
+            return false;
+        i++;
+    }
+    list_free(elemlist);
+
+    /* loop through the function names we have defined as reserved
+     * lookup the oid of the function so that we can use this for future
+     * evaluations rather than comparing strings
+     * and find the maximum oid
+     */
+    for (i = 0; i < num_reserved_funcs; i++)
+    {
+        /* not every builtin exists in every version, never match those */
+        reserved_func_oids[i] = InvalidOid;
+        if ((builtin = fmgr_lookupByName(reserved_func_list[i])) == NULL)
+        {
+            if (i >= NUM_RESERVED_FUNCS)
+                elog(WARNING, "aiven_gatekeeper: %s in aiven.pg_security_reserved_functions is not a builtin function, ignored", reserved_func_list[i]);
+        }
+        else
+        {
+            reserved_func_oids[i] = builtin->foid;
+            /* the first oid found can be both the min and the max, so check both */
+            if (builtin->foid < min_reserved_oid)
+            {
+                min_reserved_oid = builtin->foid;
+            }
+            if (builtin->foid > max_reserved_oid)
+            {
+                max_reserved_oid = builtin->foid;
+            }
+        }
+    }
+    return true;
+}
+
+#if GATEKEEPER_OBJECT_ACCESS_HOOK
+/* hook to check if the function being called is not in the disallowed-list
+ * obviously allow list of built-in functions would be prefered, but this list of disallowed is tiny
+ * and we want to ensure minimum impact on performance and function.
+ */
+static void
+gatekeeper_oa_hook(ObjectAccessType access,
+                   Oid classId,
+                   Oid objectId,
+                   int subId,
+                   void *arg)
+{
+    char *func_name;
+    int i;
+
+    oa_hook_called = true;
+
+    /* only check function if security agent is enabled */
+    if (pg_security_agent_enabled)
+    {
+        switch (access) // we are only interested in OAT_FUNCTION_EXECUTE, and OAT_POST_CREATE for the hook chain
+        {
+        case OAT_POST_CREATE:
+            /* tables, functions and schemas are only created by utility statements,
+             * which run inside our utility hook unless another library replaced it
+             */
+            if (utility_hook_depth == 0 && !utility_hook_called &&
+                (classId == RelationRelationId || classId == ProcedureRelationId || classId == NamespaceRelationId))
+            {
+                utility_hook_called = true;
+                report_hook_miss("ProcessUtility", "utility statement checks");
+            }
+            break;
+        case OAT_FUNCTION_EXECUTE:
+            /* this is invoked for function calls from SQL as well as for fastpath calls
+             * (libpq PQfn, used by the client side large object functions), both run
+             * InvokeFunctionExecuteHook before executing the function.
+             */
+            /* check if the objecid is within range of our reserved oids
+             * this allows faster evalation, rather than having to loop through
+             * arrays for each function call.
+             */
+            if (objectId >= min_reserved_oid && objectId <= max_reserved_oid)
+            {
+                for (i = 0; i < num_reserved_funcs; i++)
+                {
+                    /* lookup the oid to see if it is in our reserved list
+                     */
+                    if (reserved_func_oids[i] == objectId)
+                        break;
+                }
+                if (i == num_reserved_funcs)
+                    break;
+
+                /* the SQL level name makes the message more friendly. This hook runs in the
+                 * middle of executing a query, so only use the syscache here, never anything
+                 * that runs SQL and re-enters the executor (and this hook). The pg_proc row
+                 * can be gone, the denial never depends on finding it.
+                 */
+                func_name = get_func_name(objectId);
+                if (func_name == NULL)
+                    func_name = pstrdup(reserved_func_list[i]);
+
+                /* check if we are in a privileged context and disallow the function executions */
+                if ((is_strict_mode_enabled() || creating_extension || is_elevated() || is_security_restricted()) &&
+                    !reserved_bypass_allowed("function"))
+                {
+                    gatekeeper_deny("FUNCTION EXECUTE", restricted_context_reason(), "using builtin function %s is not allowed (function %s)", reserved_func_list[i], func_name);
+                }
+                /* extra check, this is to enforce only superuser can call this function in normal
+                 * context. Otherwise PG uses the grant system, which could lead to roles being
+                 * granted execute privilege on the funcion and still being able to call it.
+                 * This is not too serious, since non-superusers can't read outside reserved paths (for example)
+                 * but rather be strict.
+                 */
+                else if (!superuser())
+                {
+                    gatekeeper_deny("FUNCTION EXECUTE", "not_superuser", "using builtin function %s is not allowed by non-superusers (function %s)", reserved_func_list[i], func_name);
+                }
+                pfree(func_name);
+            }
+            break;
+        default:
+            break;
+        }
+    }
+
+    if (next_object_access_hook)
+        (*next_object_access_hook)(access, classId, objectId, subId, arg);
+}
+#endif
+
+#if GATEKEEPER_EXECUTOR_HOOK
+/* statement type for the audit records of the executor checks */
+static const char *
+command_type_name(CmdType operation)
+{
+    switch (operation)
+    {
+    case CMD_SELECT:
+        return "SELECT";
+    case CMD_INSERT:
+        return "INSERT";
+    case CMD_UPDATE:
+        return "UPDATE";
+    case CMD_DELETE:
+        return "DELETE";
+#if PG15_GTE
+    case CMD_MERGE:
+        return "MERGE";
+#endif
+    default:
+        return "UNKNOWN";
+    }
+}
+
+/* cap the statement timeout for the roles in aiven.pg_security_timeout_roles.
+ * The timer of the running statement is already armed by the time the executor runs, so it is
+ * armed again with the cap when it isn't running or would fire later. statement_timeout itself
+ * isn't changed, a SET by the role doesn't get around the cap. Returns the finish time of the
+ * statement timeout that was replaced, 0 when there was none, or -1 when nothing was armed.
+ */
+static TimestampTz
+arm_forced_statement_timeout(void)
+{
+    TimestampTz finish_time = 0;
+    char *current_user;
+    char *session_user;
+    long secs;
+    int usecs;
+
+    if (forced_statement_timeout <= 0 || forced_timeout_armed)
+        return -1;
+    /* the leader of a parallel query is already capped */
+    if (IsParallelWorker())
+        return -1;
+
+    if (get_timeout_active(STATEMENT_TIMEOUT))
+    {
+        finish_time = get_timeout_finish_time(STATEMENT_TIMEOUT);
+        TimestampDifference(GetCurrentTimestamp(), finish_time, &secs, &usecs);
+        /* already fires within the cap */
+        if (secs * 1000 + usecs / 1000 <= forced_statement_timeout)
+            return -1;
+    }
+
+    /* check both, so SET ROLE can't be used to get away from the cap */
+    current_user = GetUserNameFromId(GetUserId(), false);
+    session_user = GetUserNameFromId(GetSessionUserId(), false);
+    if (!identifier_list_contains(timeout_roles, current_user) &&
+        !identifier_list_contains(timeout_roles, session_user))
+        return -1;
+
+    enable_timeout_after(STATEMENT_TIMEOUT, forced_statement_timeout);
+    forced_timeout_armed = true;
+    return finish_time;
+}
+
+/* put back the statement timeout replaced by arm_forced_statement_timeout, postgres
+ * disarms it at the end of the statement only when it armed it itself
+ */
+static void
+disarm_forced_statement_timeout(TimestampTz finish_time)
+{
+    forced_timeout_armed = false;
+    disable_timeout(STATEMENT_TIMEOUT, false);
+    if (finish_time > 0)
+        enable_timeout_at(STATEMENT_TIMEOUT, finish_time);
+}
+
+/* the executor checks, split from the hook so that a monitor only build
+ * still starts the executor after a denial
+ */
+static void
+executor_start_checks(QueryDesc *queryDesc)
+{
+    /* check if there is an attempt to modify the pg_proc table
+     * this should never happen directly in extension installs
+     * or elevated context. The reserved roles are allowed to modify pg_proc, but
+     * probably don't want to be doing this manually.
+     */
+    ListCell *resultRelations;
+    RangeTblEntry *rt;
+    Bitmapset *colset;
+    int index;
+    const char *reason;
+#if PG16_GTE
+    List *permInfos;
+    RTEPermissionInfo *permInfo;
+#endif
+
+    switch (queryDesc->operation)
+    {
+    case CMD_SELECT:
+        /* nested queries of some extensions read, but don't use, the sensitive columns */
+        if (BUG_01)
+            break;
+#if PG16_GTE
+        permInfos = queryDesc->plannedstmt->permInfos;
+#endif
+        foreach (resultRelations, queryDesc->plannedstmt->rtable)
+        {
+            rt = lfirst(resultRelations);
+            switch (rt->relid)
+            {
+            case 1260: // pg_authid
+#if PG16_GTE
+                permInfo = getRTEPermissionInfo(permInfos, rt);
+                colset = permInfo->selectedCols;
+#else
+                colset = rt->selectedCols;
+#endif
+                index = -1;
+                while ((index = bms_next_member(colset, index)) >= 0)
+                {
+                    AttrNumber attno = index + FirstLowInvalidHeapAttributeNumber;
+                    char *attname;
+                    int i;
+
+                    /* get the column name, function definition changed with PG11 */
+#if PG11_GTE
+                    attname = get_attname(1260, attno, true);
+#else
+                    attname = get_attname(1260, attno);
+#endif
+                    /* check if column is reserved */
+                    for (i = 0; i < NUM_RESERVED_AUTH_COLS; i++)
+                    {
+                        if (strncmp(reserved_auth_col_names[i], attname, 10) == 0 && (is_strict_mode_enabled() || creating_extension || is_elevated() || is_security_restricted()))
+                        {
+                            gatekeeper_deny("SELECT", restricted_context_reason(), "Reading pg_authid sensitive column %s is not allowed in elevated context", attname);
+                            return;
+                        }
+                    }
+                }
+                break;
+            default:
+                break;
+            }
+        }
+        break;
+    case CMD_INSERT:
+    case CMD_UPDATE:
+    case CMD_DELETE:
+#if PG15_GTE
+    case CMD_MERGE:
+#endif
+#if PG16_GTE
+        permInfos = queryDesc->plannedstmt->permInfos;
+#endif
+        /* only the tables being modified, not the ones the statement reads from.
+         * Updates through a view, eg: pg_shadow, have the underlying catalog as result relation.
+         */
+        reason = restricted_context_reason();
+        if (reason == NULL)
+            reason = "caller_not_reserved";
+        foreach (resultRelations, queryDesc->plannedstmt->resultRelations)
+        {
+            rt = rt_fetch(lfirst_int(resultRelations), queryDesc->plannedstmt->rtable);
+            switch (rt->relid)
+            {
+            case 1260: // pg_authid
+            case 1261: // pg_auth_membership
+                /* direct catalog changes would give superuser without going through ALTER ROLE */
+                if (is_strict_mode_enabled() || !is_reserved_caller())
+                {
+                    gatekeeper_deny(command_type_name(queryDesc->operation), reason, "Modifying %s is only allowed for reserved roles",
+                                    rt->relid == 1260 ? "pg_authid" : "pg_auth_members");
+                    return;
+                }
+                break;
+            case 1255: // pg_proc
+                /* check columns being modified and prevent creating new internal functions
+                 * would prefer to just prevent pg_proc modification, but some extensions in contrib
+                 * actually alter pg_proc directly during install/upgrade.
+                 * block changes to proowner, prolang, prosecdef, proacl, prosrc
+                 */
+#if PG16_GTE
+                permInfo = getRTEPermissionInfo(permInfos, rt);
+                if (queryDesc->operation == CMD_INSERT)
+                    colset = permInfo->insertedCols;
+                else if (queryDesc->operation == CMD_MERGE)
+                    colset = bms_union(permInfo->insertedCols, permInfo->updatedCols);
+                else
+                    colset = permInfo->updatedCols;
+#else
+                if (queryDesc->operation == CMD_INSERT)
+                    colset = rt->insertedCols;
+#if PG15_GTE
+                /* MERGE can both insert and update */
+                else if (queryDesc->operation == CMD_MERGE)
+                    colset = bms_union(rt->insertedCols, rt->updatedCols);
+#endif
+                else
+                    colset = rt->updatedCols;
+#endif
+                index = -1;
+                while ((index = bms_next_member(colset, index)) >= 0)
+                {
+                    AttrNumber attno = index + FirstLowInvalidHeapAttributeNumber;
+                    char *attname;
+                    int i;
+
+                    /* get the column name, function definition changed with PG11 */
+#if PG11_GTE
+                    attname = get_attname(1255, attno, true);
+#else
+                    attname = get_attname(1255, attno);
+#endif
+                    /* check if column is reserved */
+                    for (i = 0; i < NUM_RESERVED_COLS; i++)
+                    {
+                        if (strncmp(reserved_col_names[i], attname, 10) == 0 && (is_strict_mode_enabled() || !is_reserved_caller()))
+                        {
+                            gatekeeper_deny(command_type_name(queryDesc->operation), reason, "Modifying pg_proc sensitive column %s is only allowed for reserved roles", attname);
+                            return;
+                        }
+                    }
+                }
+                break;
+            default:
+                break;
+            }
+        }
+        break;
+    default:
+        break;
+    }
 }
 
 static void
 pg_proc_guard_checks(QueryDesc *queryDesc, int eflags)
 {
-    /* check if there is an attempt to modify the pg_proc table
-     * this should never happen directly in extension installs
-     * or elevated context. Superuser is allowed to modify pg_proc, but
-     * probably doesn't want to be doing this manually.
-     */
-    ListCell *resultRelations;
-    RangeTblEntry *rt;
-    Bitmapset *colset;
-    int index;
-#if PG16_GTE
-    List *permInfos;
-    RTEPermissionInfo *permInfo;
+    executor_hook_called = true;
+
+    /* only check function if security agent is enabled */
+    if (pg_security_agent_enabled)
+        executor_start_checks(queryDesc);
+
+    if (prev_ExecutorStart_hook)
+        prev_ExecutorStart_hook(queryDesc, eflags);
+    else
+        standard_ExecutorStart(queryDesc, eflags);
+}
+
+/* every execution of a query is capped, including each FETCH from a cursor. Nested queries,
+ * eg: in a function, run within the cap of the outer one.
+ */
+static void
+forced_timeout_executor_run(QueryDesc *queryDesc, ScanDirection direction, uint64 count, bool execute_once)
+{
+    TimestampTz finish_time = -1;
+
+    if (pg_security_agent_enabled)
+        finish_time = arm_forced_statement_timeout();
+
+    PG_TRY();
+    {
+        if (prev_ExecutorRun_hook)
+            prev_ExecutorRun_hook(queryDesc, direction, count, execute_once);
+        else
+            standard_ExecutorRun(queryDesc, direction, count, execute_once);
+    }
+    PG_CATCH();
+    {
+        if (finish_time >= 0)
+            disarm_forced_statement_timeout(finish_time);
+        PG_RE_THROW();
+    }
+    PG_END_TRY();
+
+    if (finish_time >= 0)
+        disarm_forced_statement_timeout(finish_time);
+}
+#endif
+
+/* the value of a setting in the configuration files, as the next reload is going to see it.
+ * NULL when it isn't set there, the reload resets it to the default.
+ */
+static char *
+pending_config_value(const char *name)
+{
+    ConfigVariable *head = NULL;
+    ConfigVariable *tail = NULL;
+    ConfigVariable *item;
+    char *value = NULL;
+
+    /* same files and order as the reload, a later entry overrides an earlier one */
+    if (!ParseConfigFile(ConfigFileName, true, NULL, 0, 0, LOG, &head, &tail))
+        ereport(WARNING,
+                (errmsg("could not parse configuration file \"%s\", see the server log", ConfigFileName)));
+    else if (!ParseConfigFile(PG_AUTOCONF_FILENAME, false, NULL, 0, 0, LOG, &head, &tail))
+        ereport(WARNING,
+                (errmsg("could not parse configuration file \"%s\", see the server log", PG_AUTOCONF_FILENAME)));
+
+    for (item = head; item != NULL; item = item->next)
+    {
+        if (item->ignore || item->name == NULL || item->value == NULL)
+            continue;
+        if (pg_strcasecmp(item->name, name) == 0)
+            value = item->value;
+    }
+
+    if (value != NULL)
+        value = pstrdup(value);
+    FreeConfigVariables(head);
+    return value;
+}
+
+/* requests a configuration reload and reports the reserved roles the reload puts in effect,
+ * gives operators confirmation that the policy matches the config file after an edit.
+ * The reload is applied by every backend once it is idle, same as pg_reload_conf(), applying it
+ * here would be undone again when the transaction calling the function aborts. The roles are
+ * resolved from the configuration files the way the reload is going to, a value the reload
+ * refuses keeps the current one.
+ */
+PG_FUNCTION_INFO_V1(aiven_gatekeeper_reload);
+
+Datum
+aiven_gatekeeper_reload(PG_FUNCTION_ARGS)
+{
+    const char *current_setting;
+    char *pending_setting;
+    const char *effective_setting;
+    char *pending_file;
+    const char *pending_file_entries;
+    List *elemlist;
+    List *pending_list;
+    ListCell *role;
+    StringInfoData names;
+    char *regex_error;
+    Datum *elems;
+    int nelems = 0;
+
+    if (!is_reserved_caller())
+        elog(ERROR, "aiven_gatekeeper_reload() is only allowed for reserved roles");
+
+    /* signal the postmaster so that all backends, including this one, pick up the change */
+    if (kill(PostmasterPid, SIGHUP))
+        elog(WARNING, "failed to send signal to postmaster: %m");
+
+    current_setting = allowed_superuser_roles ? allowed_superuser_roles : "";
+    pending_setting = pending_config_value("aiven.pg_security_agent_reserved_roles");
+    if (pending_setting == NULL)
+        pending_setting = pstrdup(DEFAULT_RESERVED_ROLE);
+
+    /* the reload keeps the current value when the new one is refused */
+    effective_setting = pending_setting;
+    if (strcmp(pending_setting, current_setting) != 0)
+    {
+        if (pg_security_agent_strict)
+        {
+            ereport(WARNING,
+                    (errmsg("aiven.pg_security_agent_reserved_roles can't be changed in strict mode, the reload keeps \"%s\"", current_setting)));
+            effective_setting = current_setting;
+        }
+        else if (!split_identifier_list(pending_setting, &pending_list, NULL))
+        {
+            ereport(WARNING,
+                    (errmsg("aiven.pg_security_agent_reserved_roles \"%s\" has an invalid list syntax, the reload keeps \"%s\"", pending_setting, current_setting)));
+            effective_setting = current_setting;
+        }
+        else if ((regex_error = invalid_role_regex(pending_list)) != NULL)
+        {
+            ereport(WARNING,
+                    (errmsg("aiven.pg_security_agent_reserved_roles \"%s\" is invalid, the reload keeps \"%s\"", pending_setting, current_setting),
+                     errdetail("%s", regex_error)));
+            effective_setting = current_setting;
+        }
+    }
+
+    /* the reload reads the file again, strict mode refuses the setting and keeps the entries read before */
+    if (pg_security_agent_strict)
+        pending_file_entries = reserved_roles_from_file;
+    else
+    {
+        pending_file = pending_config_value("aiven.pg_security_config_file");
+        if (pending_file != NULL && pending_file[0] != '\0')
+            pending_file_entries = read_reserved_roles_file(pending_file);
+        else
+            pending_file_entries = NULL;
+    }
+
+    /* the roles resolved from the setting and aiven.pg_security_config_file after the reload */
+    elemlist = resolve_reserved_roles(merge_reserved_roles_setting(effective_setting, pending_file_entries));
+    initStringInfo(&names);
+    foreach (role, elemlist)
+    {
+        appendStringInfo(&names, "%s%s", names.len > 0 ? ", " : "", (char *)lfirst(role));
+    }
+
+    if (strcmp(pending_setting, current_setting) != 0)
+        elog(LOG, "aiven.pg_security_agent_reserved_roles changes from \"%s\" to \"%s\" on reload, reserved roles after the reload \"%s\"",
+             current_setting, pending_setting, names.data);
+    else
+        elog(LOG, "aiven.pg_security_agent_reserved_roles unchanged \"%s\", reserved roles after the reload \"%s\"",
+             current_setting, names.data);
+
+    elems = (Datum *)palloc(Max(list_length(elemlist), 1) * sizeof(Datum));
+    foreach (role, elemlist)
+    {
+        elems[nelems++] = CStringGetTextDatum((char *)lfirst(role));
+    }
+
+    PG_RETURN_ARRAYTYPE_P(construct_array(elems, nelems, TEXTOID, -1, false, 'i'));
+}
+
+/* report for each of our hooks whether another library installed its hook before us
+ * (has_previous, we chain to it) and whether we are still the installed hook (installed_last,
+ * nobody chained after us). A hook that was compiled out is reported as NULL.
+ */
+PG_FUNCTION_INFO_V1(aiven_gatekeeper_hook_chain);
+
+Datum
+aiven_gatekeeper_hook_chain(PG_FUNCTION_ARGS)
+{
+    TupleDesc tupdesc;
+    Datum values[6];
+    bool nulls[6];
+
+    if (get_call_result_type(fcinfo, NULL, &tupdesc) != TYPEFUNC_COMPOSITE)
+        elog(ERROR, "return type must be a row type");
+
+    memset(nulls, 0, sizeof(nulls));
+
+    values[0] = BoolGetDatum(prev_ProcessUtility != NULL);
+    values[1] = BoolGetDatum(ProcessUtility_hook == gatekeeper_checks);
+#if GATEKEEPER_EXECUTOR_HOOK
+    values[2] = BoolGetDatum(prev_ExecutorStart_hook != NULL);
+    values[3] = BoolGetDatum(ExecutorStart_hook == pg_proc_guard_checks);
+#else
+    nulls[2] = nulls[3] = true;
+    values[2] = values[3] = (Datum)0;
 #endif
-    /* only check function if security agent is enabled */
-    if (pg_security_agent_enabled && !BUG_01)
+#if GATEKEEPER_OBJECT_ACCESS_HOOK
+    values[4] = BoolGetDatum(next_object_access_hook != NULL);
+    values[5] = BoolGetDatum(object_access_hook == gatekeeper_oa_hook);
+#else
+    nulls[4] = nulls[5] = true;
+    values[4] = values[5] = (Datum)0;
+#endif
+
+    tupdesc = BlessTupleDesc(tupdesc);
+    PG_RETURN_DATUM(HeapTupleGetDatum(heap_form_tuple(tupdesc, values, nulls)));
+}
+
+/* report the context the checks see for the current session */
+PG_FUNCTION_INFO_V1(aiven_gatekeeper_context);
+
+Datum
+aiven_gatekeeper_context(PG_FUNCTION_ARGS)
+{
+    TupleDesc tupdesc;
+    Datum values[4];
+    bool nulls[4];
+
+    if (!superuser())
+        ereport(ERROR,
+                (errcode(ERRCODE_INSUFFICIENT_PRIVILEGE),
+                 errmsg("aiven_gatekeeper_context() is only allowed for superusers")));
+
+    if (get_call_result_type(fcinfo, NULL, &tupdesc) != TYPEFUNC_COMPOSITE)
+        elog(ERROR, "return type must be a row type");
+
+    memset(nulls, 0, sizeof(nulls));
+    values[0] = BoolGetDatum(is_elevated());
+    values[1] = BoolGetDatum(is_security_restricted());
+    values[2] = BoolGetDatum(is_strict_mode_enabled());
+    values[3] = BoolGetDatum(pg_security_agent_enabled);
+
+    tupdesc = BlessTupleDesc(tupdesc);
+    PG_RETURN_DATUM(HeapTupleGetDatum(heap_form_tuple(tupdesc, values, nulls)));
+}
+
+/* report how the checks classify a role, eg: to find out why a statement on it was denied.
+ * A role that doesn't exist can still match a reserved role entry, the other columns are NULL then.
+ */
+PG_FUNCTION_INFO_V1(aiven_gatekeeper_role_info);
+
+Datum
+aiven_gatekeeper_role_info(PG_FUNCTION_ARGS)
+{
+    TupleDesc tupdesc;
+    Datum values[5];
+    bool nulls[5];
+    char *rolename;
+    Oid roleoid;
+
+    if (!superuser())
+        ereport(ERROR,
+                (errcode(ERRCODE_INSUFFICIENT_PRIVILEGE),
+                 errmsg("aiven_gatekeeper_role_info() is only allowed for superusers")));
+
+    if (get_call_result_type(fcinfo, NULL, &tupdesc) != TYPEFUNC_COMPOSITE)
+        elog(ERROR, "return type must be a row type");
+
+    rolename = text_to_cstring(PG_GETARG_TEXT_PP(0));
+    roleoid = get_role_oid(rolename, true);
+
+    memset(nulls, 0, sizeof(nulls));
+    values[0] = ObjectIdGetDatum(roleoid);
+    values[1] = BoolGetDatum(allow_superuser_role(rolename));
+    values[2] = BoolGetDatum(identifier_list_contains(critical_login_roles, rolename));
+    if (OidIsValid(roleoid))
     {
-        switch (queryDesc->operation)
-        {
-        case CMD_SELECT:
-#if PG16_GTE
-	    permInfos = queryDesc->plannedstmt->permInfos;
+        values[3] = BoolGetDatum(superuser_arg(roleoid));
+        values[4] = BoolGetDatum(is_restricted_role(roleoid));
+    }
+    else
+    {
+        nulls[0] = true;
+        nulls[3] = true;
+        nulls[4] = true;
+    }
+
+    tupdesc = BlessTupleDesc(tupdesc);
+    PG_RETURN_DATUM(HeapTupleGetDatum(heap_form_tuple(tupdesc, values, nulls)));
+}
+
+/* compare without returning early, so the time taken doesn't tell how much of the token matched */
+static bool
+break_glass_token_matches(const char *token, const char *secret)
+{
+    size_t token_len = strlen(token);
+    size_t secret_len = strlen(secret);
+    size_t i;
+    unsigned char diff = token_len != secret_len;
+
+    for (i = 0; i < secret_len; i++)
+        diff |= (unsigned char)secret[i] ^ (unsigned char)(i < token_len ? token[i] : 0);
+
+    return diff == 0;
+}
+
+/* lifts strict mode for the rest of the current session only, for break glass access
+ * when the configuration can't be changed. Needs a reserved role and the secret
+ * in aiven.pg_security_break_glass_secret.
+ */
+PG_FUNCTION_INFO_V1(aiven_gatekeeper_break_glass);
+
+Datum
+aiven_gatekeeper_break_glass(PG_FUNCTION_ARGS)
+{
+    char *token = text_to_cstring(PG_GETARG_TEXT_PP(0));
+
+    if (break_glass_secret == NULL || break_glass_secret[0] == '\0')
+        ereport(ERROR,
+                (errcode(ERRCODE_FEATURE_NOT_SUPPORTED),
+                 errmsg("aiven_gatekeeper_break_glass() is not configured"),
+                 errhint("Set aiven.pg_security_break_glass_secret and restart the server.")));
+
+    if (!is_reserved_caller())
+    {
+        gatekeeper_deny("BREAK GLASS", "caller_not_reserved", "aiven_gatekeeper_break_glass() is only allowed for reserved roles");
+        PG_RETURN_BOOL(false);
+    }
+    if (!break_glass_token_matches(token, break_glass_secret))
+    {
+        gatekeeper_deny("BREAK GLASS", "invalid_token", "invalid break glass token");
+        PG_RETURN_BOOL(false);
+    }
+
+    break_glass_active = true;
+    gatekeeper_audit_log(WARNING, "BREAK GLASS", "break_glass", "allow",
+                         psprintf("strict mode lifted for this session (pid %d) by break glass", MyProcPid));
+    PG_RETURN_BOOL(true);
+}
+
+static void
+put_list_entries(Tuplestorestate *tupstore, TupleDesc tupdesc, const char *list_name, List *entries)
+{
+    ListCell *cell;
+    Datum values[2];
+    bool nulls[2];
+
+    memset(nulls, 0, sizeof(nulls));
+    values[0] = CStringGetTextDatum(list_name);
+    foreach (cell, entries)
+    {
+        values[1] = CStringGetTextDatum((char *)lfirst(cell));
+        tuplestore_putvalues(tupstore, tupdesc, values, nulls);
+    }
+}
+
+/* report the entries of the list settings the way the checks parse them,
+ * empty and duplicate entries are left out, unquoted names are lower case
+ */
+PG_FUNCTION_INFO_V1(aiven_gatekeeper_lists);
+
+Datum
+aiven_gatekeeper_lists(PG_FUNCTION_ARGS)
+{
+    ReturnSetInfo *rsinfo = (ReturnSetInfo *)fcinfo->resultinfo;
+    TupleDesc tupdesc;
+    Tuplestorestate *tupstore;
+    MemoryContext oldcontext;
+    List *elemlist;
+    List *entries;
+    ListCell *cell;
+    char *rawstring;
+    char *schema;
+    char *table;
+    char *column;
+
+    if (!superuser())
+        ereport(ERROR,
+                (errcode(ERRCODE_INSUFFICIENT_PRIVILEGE),
+                 errmsg("aiven_gatekeeper_lists() is only allowed for superusers")));
+
+    if (rsinfo == NULL || !IsA(rsinfo, ReturnSetInfo) || !(rsinfo->allowedModes & SFRM_Materialize))
+        ereport(ERROR,
+                (errcode(ERRCODE_FEATURE_NOT_SUPPORTED),
+                 errmsg("set-valued function called in context that cannot accept a set")));
+
+    oldcontext = MemoryContextSwitchTo(rsinfo->econtext->ecxt_per_query_memory);
+    if (get_call_result_type(fcinfo, NULL, &tupdesc) != TYPEFUNC_COMPOSITE)
+        elog(ERROR, "return type must be a row type");
+    tupstore = tuplestore_begin_heap(true, false, work_mem);
+    rsinfo->returnMode = SFRM_Materialize;
+    rsinfo->setResult = tupstore;
+    rsinfo->setDesc = tupdesc;
+    MemoryContextSwitchTo(oldcontext);
+
+    /* the reserved roles in effect, including the strict mode fallback */
+    put_list_entries(tupstore, tupdesc, "reserved_roles", get_reserved_roles());
+
+    /* the part of them read from aiven.pg_security_config_file at the last reload */
+    split_identifier_list(reserved_roles_from_file, &elemlist, NULL);
+    put_list_entries(tupstore, tupdesc, "reserved_roles_file", elemlist);
+
+    /* only the additional ones, the builtin reserved functions are fixed */
+    split_identifier_list(extra_reserved_functions, &elemlist, NULL);
+    put_list_entries(tupstore, tupdesc, "reserved_functions", elemlist);
+
+    split_identifier_list(protected_schemas, &elemlist, NULL);
+    put_list_entries(tupstore, tupdesc, "protected_schemas", elemlist);
+
+    split_identifier_list(protected_role_attributes, &elemlist, NULL);
+    put_list_entries(tupstore, tupdesc, "protected_role_attributes", elemlist);
+
+    split_identifier_list(critical_login_roles, &elemlist, NULL);
+    put_list_entries(tupstore, tupdesc, "critical_login_roles", elemlist);
+
+    split_identifier_list(guarded_role_attributes, &elemlist, NULL);
+    put_list_entries(tupstore, tupdesc, "guarded_role_attributes", elemlist);
+
+    split_identifier_list(reserved_bypass, &elemlist, NULL);
+    put_list_entries(tupstore, tupdesc, "reserved_bypass", elemlist);
+
+    split_identifier_list(bypass_roles, &elemlist, NULL);
+    put_list_entries(tupstore, tupdesc, "bypass_roles", elemlist);
+
+    split_identifier_list(extension_policy, &elemlist, NULL);
+    put_list_entries(tupstore, tupdesc, "extension_policy", elemlist);
+
+#if GATEKEEPER_EXECUTOR_HOOK
+    split_identifier_list(timeout_roles, &elemlist, NULL);
+    put_list_entries(tupstore, tupdesc, "timeout_roles", elemlist);
 #endif
-            foreach (resultRelations, queryDesc->plannedstmt->rtable)
-            {
-                rt = lfirst(resultRelations);
-                switch (rt->relid)
-                {
-                case 1260: // pg_authid
-#if PG16_GTE
-                    permInfo = getRTEPermissionInfo(permInfos, rt);
-                    colset = permInfo->selectedCols;
+
+    /* the paths as they are compared, absolute and normalised */
+    entries = NIL;
+    rawstring = pstrdup(copy_allowed_paths ? copy_allowed_paths : "");
+    if (SplitDirectoriesString(rawstring, ',', &elemlist))
+    {
+        foreach (cell, elemlist)
+        {
+            if (((char *)lfirst(cell))[0] != '\0')
+                entries = lappend(entries, copy_absolute_path((char *)lfirst(cell)));
+        }
+    }
+    put_list_entries(tupstore, tupdesc, "copy_allowed_paths", entries);
+
+    /* only the entries that can be parsed as schema.table.column */
+    entries = NIL;
+    split_identifier_list(sensitive_columns, &elemlist, NULL);
+    foreach (cell, elemlist)
+    {
+        if (gatekeeper_parse_sensitive_column((char *)lfirst(cell), &schema, &table, &column))
+            entries = lappend(entries, psprintf("%s.%s.%s", schema, table, column));
+    }
+    put_list_entries(tupstore, tupdesc, "sensitive_columns", entries);
+
+    return (Datum)0;
+}
+
+/* list the statement node types with at least one registered check, in registration order.
+ * Types registered by other libraries without a known name are shown by their number.
+ */
+PG_FUNCTION_INFO_V1(aiven_gatekeeper_covered_statements);
+
+Datum
+aiven_gatekeeper_covered_statements(PG_FUNCTION_ARGS)
+{
+    ReturnSetInfo *rsinfo = (ReturnSetInfo *)fcinfo->resultinfo;
+    TupleDesc tupdesc;
+    Tuplestorestate *tupstore;
+    MemoryContext oldcontext;
+    Datum values[1];
+    bool nulls[1];
+    const char *name;
+    int i;
+    int j;
+
+    if (!superuser())
+        ereport(ERROR,
+                (errcode(ERRCODE_INSUFFICIENT_PRIVILEGE),
+                 errmsg("aiven_gatekeeper_covered_statements() is only allowed for superusers")));
+
+    if (rsinfo == NULL || !IsA(rsinfo, ReturnSetInfo) || !(rsinfo->allowedModes & SFRM_Materialize))
+        ereport(ERROR,
+                (errcode(ERRCODE_FEATURE_NOT_SUPPORTED),
+                 errmsg("set-valued function called in context that cannot accept a set")));
+
+    oldcontext = MemoryContextSwitchTo(rsinfo->econtext->ecxt_per_query_memory);
+#if PG12_GTE
+    tupdesc = CreateTemplateTupleDesc(1);
 #else
-                    colset = rt->selectedCols;
+    tupdesc = CreateTemplateTupleDesc(1, false);
 #endif
-                    index = -1;
-                    while ((index = bms_next_member(colset, index)) >= 0)
-                    {
-                        AttrNumber attno = index + FirstLowInvalidHeapAttributeNumber;
-                        char *attname;
-                        int i;
+    TupleDescInitEntry(tupdesc, (AttrNumber)1, "aiven_gatekeeper_covered_statements", TEXTOID, -1, 0);
+    tupstore = tuplestore_begin_heap(true, false, work_mem);
+    rsinfo->returnMode = SFRM_Materialize;
+    rsinfo->setResult = tupstore;
+    rsinfo->setDesc = tupdesc;
+    MemoryContextSwitchTo(oldcontext);
 
-                        /* get the column name, function definition changed with PG11 */
-#if PG11_GTE
-                        attname = get_attname(1260, attno, true);
-#else
-                        attname = get_attname(1260, attno);
+    memset(nulls, 0, sizeof(nulls));
+    for (i = 0; i < num_utility_checks; i++)
+    {
+        // a type with several checks is listed once
+        for (j = 0; j < i; j++)
+        {
+            if (utility_checks[j].tag == utility_checks[i].tag)
+                break;
+        }
+        if (j < i)
+            continue;
+
+        // the default checks are registered first, they name the type
+        name = utility_checks[i].name;
+        if (name == NULL)
+            name = psprintf("node type %d", (int)utility_checks[i].tag);
+
+        values[0] = CStringGetTextDatum(name);
+        tuplestore_putvalues(tupstore, tupdesc, values, nulls);
+    }
+
+    return (Datum)0;
+}
+
+#if GATEKEEPER_TEST_API
+/* Test only API, see aiven_gatekeeper.h.
+ *
+ * Safety invariants the synthetic statements rely on:
+ * - the nodes come from makeNode, every field that isn't set is zeroed, which is
+ *   what the parser produces for an absent clause (NULL relation, NIL options)
+ * - only fields the grammar can set for that statement shape are filled in, a
+ *   check never has to handle a combination the parser can't produce
+ * - the checks look things up in the catalogs, so they have to run inside a
+ *   transaction, which the SQL callable wrappers always do
+ * - the statement is never executed, a denial raises the same ERROR as the real
+ *   statement would and leaves nothing behind
+ */
+Node *
+aiven_gatekeeper_test_create_role_stmt(const char *rolename, const char *option, const char *value)
+{
+    CreateRoleStmt *createRoleStmt = makeNode(CreateRoleStmt);
+
+    createRoleStmt->stmt_type = ROLESTMT_ROLE;
+    createRoleStmt->role = pstrdup(rolename);
+    /* a value of NULL is the bare keyword, eg: CREATE ROLE x SUPERUSER */
+    if (option != NULL)
+        createRoleStmt->options = list_make1(makeDefElem(pstrdup(option),
+                                                         value ? (Node *)makeString(pstrdup(value)) : NULL,
+                                                         -1));
+    return (Node *)createRoleStmt;
+}
+
+Node *
+aiven_gatekeeper_test_copy_stmt(const char *relname, const char *filename, bool is_from, bool is_program)
+{
+    CopyStmt *copyStmt = makeNode(CopyStmt);
+
+    copyStmt->relation = makeRangeVar(NULL, pstrdup(relname), -1);
+    copyStmt->is_from = is_from;
+    copyStmt->is_program = is_program;
+    /* NULL is STDIN/STDOUT */
+    copyStmt->filename = filename ? pstrdup(filename) : NULL;
+    return (Node *)copyStmt;
+}
+
+void
+aiven_gatekeeper_test_run_checks(Node *stmt, ProcessUtilityContext context)
+{
+    ProcessUtilityContext saved_context = current_utility_context;
+
+    current_utility_context = context;
+    PG_TRY();
+    {
+        run_utility_checks(stmt);
+    }
+    PG_CATCH();
+    {
+        current_utility_context = saved_context;
+        PG_RE_THROW();
+    }
+    PG_END_TRY();
+    current_utility_context = saved_context;
+}
+
+/* runs a synthetic CREATE ROLE through the checks as if sent by the client,
+ * returns true when all checks passed
+ */
+PG_FUNCTION_INFO_V1(aiven_gatekeeper_test_create_role);
+
+Datum
+aiven_gatekeeper_test_create_role(PG_FUNCTION_ARGS)
+{
+    char *rolename;
+    char *option = NULL;
+    char *value = NULL;
+
+    if (PG_ARGISNULL(0))
+        elog(ERROR, "role name must not be NULL");
+    rolename = text_to_cstring(PG_GETARG_TEXT_PP(0));
+    if (!PG_ARGISNULL(1))
+        option = text_to_cstring(PG_GETARG_TEXT_PP(1));
+    if (!PG_ARGISNULL(2))
+        value = text_to_cstring(PG_GETARG_TEXT_PP(2));
+
+    aiven_gatekeeper_test_run_checks(aiven_gatekeeper_test_create_role_stmt(rolename, option, value), PROCESS_UTILITY_TOPLEVEL);
+    PG_RETURN_BOOL(true);
+}
+
+/* same for a synthetic COPY */
+PG_FUNCTION_INFO_V1(aiven_gatekeeper_test_copy);
+
+Datum
+aiven_gatekeeper_test_copy(PG_FUNCTION_ARGS)
+{
+    char *relname;
+    char *filename = NULL;
+
+    if (PG_ARGISNULL(0) || PG_ARGISNULL(2) || PG_ARGISNULL(3))
+        elog(ERROR, "relation name, is_from and is_program must not be NULL");
+    relname = text_to_cstring(PG_GETARG_TEXT_PP(0));
+    if (!PG_ARGISNULL(1))
+        filename = text_to_cstring(PG_GETARG_TEXT_PP(1));
+
+    aiven_gatekeeper_test_run_checks(aiven_gatekeeper_test_copy_stmt(relname, filename, PG_GETARG_BOOL(2), PG_GETARG_BOOL(3)), PROCESS_UTILITY_TOPLEVEL);
+    PG_RETURN_BOOL(true);
+}
 #endif
-                        /* check if column is reserved */
-                        for (i = 0; i < NUM_RESERVED_AUTH_COLS; i++)
-                        {
-                            if (strncmp(reserved_auth_col_names[i], attname, 10) == 0 && (pg_security_agent_strict || creating_extension || is_elevated() || is_security_restricted()))
-                            {
-                                elog(ERROR, "Reading pg_authid sensitive columns is not allowed in elevated context");
-                                return;
-                            }
-                        }
-                    }
-                    break;
-                default:
-                    break;
-                }
-            }
-            break;
-        case CMD_INSERT:
-        case CMD_UPDATE:
-        case CMD_DELETE:
-#if PG16_GTE
-	    permInfos = queryDesc->plannedstmt->permInfos;
+
+
+static void
+gatekeeper_shmem_request(void)
+{
+#if PG15_GTE
+    if (prev_shmem_request_hook)
+        prev_shmem_request_hook();
 #endif
-            foreach (resultRelations, queryDesc->plannedstmt->rtable)
+    RequestAddinShmemSpace(MAXALIGN(sizeof(GatekeeperSharedState)));
+    RequestNamedLWLockTranche("aiven_gatekeeper", 1);
+}
+
+static void
+gatekeeper_shmem_startup(void)
+{
+    bool found;
+
+    if (prev_shmem_startup_hook)
+        prev_shmem_startup_hook();
+
+    LWLockAcquire(AddinShmemInitLock, LW_EXCLUSIVE);
+    gatekeeper_state = ShmemInitStruct("aiven_gatekeeper", sizeof(GatekeeperSharedState), &found);
+    if (!found)
+    {
+        memset(gatekeeper_state, 0, sizeof(GatekeeperSharedState));
+        gatekeeper_state->lock = &(GetNamedLWLockTranche("aiven_gatekeeper"))->lock;
+    }
+    LWLockRelease(AddinShmemInitLock);
+}
+
+static void
+notify_worker_sighup(SIGNAL_ARGS)
+{
+    int save_errno = errno;
+
+    notify_worker_got_sighup = true;
+    SetLatch(MyLatch);
+
+    errno = save_errno;
+}
+
+static void
+notify_worker_exit(int code, Datum arg)
+{
+    LWLockAcquire(gatekeeper_state->lock, LW_EXCLUSIVE);
+    gatekeeper_state->worker_latch = NULL;
+    LWLockRelease(gatekeeper_state->lock);
+}
+
+/* background worker sending the queued denials as NOTIFY on aiven.pg_security_denial_notify_channel.
+ * A NOTIFY from the denied statement itself would never be delivered, the denial aborts
+ * its transaction, so the worker sends them from its own transactions instead.
+ */
+void
+aiven_gatekeeper_notify_main(Datum main_arg)
+{
+    char payload[DENIAL_PAYLOAD_LEN];
+    bool have_payload;
+    uint64 dropped;
+    int rc;
+
+    pqsignal(SIGHUP, notify_worker_sighup);
+    BackgroundWorkerUnblockSignals();
+    BackgroundWorkerInitializeConnection(denial_notify_database, NULL, 0);
+
+    LWLockAcquire(gatekeeper_state->lock, LW_EXCLUSIVE);
+    gatekeeper_state->worker_latch = MyLatch;
+    LWLockRelease(gatekeeper_state->lock);
+    on_shmem_exit(notify_worker_exit, (Datum)0);
+
+    for (;;)
+    {
+        rc = WaitLatch(MyLatch, WL_LATCH_SET | WL_TIMEOUT | WL_POSTMASTER_DEATH, 10000L, PG_WAIT_EXTENSION);
+        if (rc & WL_POSTMASTER_DEATH)
+            proc_exit(1);
+        ResetLatch(MyLatch);
+        CHECK_FOR_INTERRUPTS();
+
+        if (notify_worker_got_sighup)
+        {
+            notify_worker_got_sighup = false;
+            ProcessConfigFile(PGC_SIGHUP);
+        }
+
+        /* send everything in the queue, one transaction per denial so that a
+         * failing NOTIFY doesn't hold back the others
+         */
+        for (;;)
+        {
+            LWLockAcquire(gatekeeper_state->lock, LW_EXCLUSIVE);
+            have_payload = gatekeeper_state->queue_tail < gatekeeper_state->queue_head;
+            if (have_payload)
             {
-                rt = lfirst(resultRelations);
-                switch (rt->relid)
-                {
-                case 1260: // pg_authid
-                case 1261: // pg_auth_membership
-                    if (pg_security_agent_strict || creating_extension || is_elevated() || is_security_restricted())
-                    {
-                        elog(ERROR, "Modifying pg_authid or pg_auth_members is not allowed in elevated context");
-                        return;
-                    }
-                    break;
-                case 1255: // pg_proc
-                    /* check columns being modified and prevent creating new internal functions
-                     * would prefer to just prevent pg_proc modification, but some extensions in contrib
-                     * actually alter pg_proc directly during install/upgrade.
-                     * block changes to proowner, prolang, prosecdef, proacl, prosrc
-                     */
-#if PG16_GTE
-                    permInfo = getRTEPermissionInfo(permInfos, rt);
-                    if (queryDesc->operation == CMD_INSERT)
-                        colset = permInfo->insertedCols;
-                    else
-                        colset = permInfo->updatedCols;
-#else
-                    if (queryDesc->operation == CMD_INSERT)
-                        colset = rt->insertedCols;
-                    else
-                        colset = rt->updatedCols;
-#endif
-                    index = -1;
-                    while ((index = bms_next_member(colset, index)) >= 0)
-                    {
-                        AttrNumber attno = index + FirstLowInvalidHeapAttributeNumber;
-                        char *attname;
-                        int i;
+                strlcpy(payload, gatekeeper_state->queue[gatekeeper_state->queue_tail % DENIAL_QUEUE_SIZE], DENIAL_PAYLOAD_LEN);
+                gatekeeper_state->queue_tail++;
+            }
+            dropped = gatekeeper_state->queue_dropped;
+            gatekeeper_state->queue_dropped = 0;
+            LWLockRelease(gatekeeper_state->lock);
 
-                        /* get the column name, function definition changed with PG11 */
-#if PG11_GTE
-                        attname = get_attname(1255, attno, true);
-#else
-                        attname = get_attname(1255, attno);
+            if (dropped > 0)
+                elog(LOG, "aiven_gatekeeper: " UINT64_FORMAT " denial notifications dropped, queue was full", dropped);
+            if (!have_payload)
+                break;
+
+            SetCurrentStatementStartTimestamp();
+            StartTransactionCommand();
+            pgstat_report_activity(STATE_RUNNING, "sending denial notification");
+            Async_Notify(denial_notify_channel, payload);
+            CommitTransactionCommand();
+#if !PG15_GTE
+            /* older versions only signal the listeners from the main loop of a regular backend */
+            ProcessCompletedNotifies();
 #endif
-                        /* check if column is reserved */
-                        for (i = 0; i < NUM_RESERVED_COLS; i++)
-                        {
-                            if (strncmp(reserved_col_names[i], attname, 10) == 0 && (pg_security_agent_strict || creating_extension || is_elevated() || is_security_restricted()))
-                            {
-                                elog(ERROR, "Modifying pg_proc sensitive columns is not allowed in elevated context");
-                                return;
-                            }
-                        }
-                    }
-                    break;
-                default:
-                    break;
-                }
-            }
-            break;
-        default:
-            break;
+            pgstat_report_activity(STATE_IDLE, NULL);
+        }
+    }
+}
+
+static void
+metrics_worker_sighup(SIGNAL_ARGS)
+{
+    int save_errno = errno;
+
+    metrics_worker_got_sighup = true;
+    SetLatch(MyLatch);
+
+    errno = save_errno;
+}
+
+/* the counters in the prometheus text format, for aiven.pg_security_metrics_file */
+static void
+write_metrics_file(uint64 denials_total, StatementMetric *metrics, int num_metrics)
+{
+    StringInfoData buf;
+    char *tmppath;
+    FILE *file;
+    int i;
+
+    initStringInfo(&buf);
+    appendStringInfoString(&buf, "# HELP aiven_gatekeeper_denials_total Statements denied since startup.\n");
+    appendStringInfoString(&buf, "# TYPE aiven_gatekeeper_denials_total counter\n");
+    appendStringInfo(&buf, "aiven_gatekeeper_denials_total " UINT64_FORMAT "\n", denials_total);
+    appendStringInfoString(&buf, "# HELP aiven_gatekeeper_statement_denials_total Statements denied since startup, per statement type.\n");
+    appendStringInfoString(&buf, "# TYPE aiven_gatekeeper_statement_denials_total counter\n");
+    for (i = 0; i < num_metrics; i++)
+        appendStringInfo(&buf, "aiven_gatekeeper_statement_denials_total{statement_type=\"%s\"} " UINT64_FORMAT "\n",
+                         metrics[i].statement_type, metrics[i].denials);
+
+    /* write to a temporary file and rename it, a scraper never sees a partial file */
+    tmppath = psprintf("%s.tmp", metrics_file);
+    file = AllocateFile(tmppath, "w");
+    if (file == NULL)
+    {
+        ereport(LOG,
+                (errcode_for_file_access(),
+                 errmsg("could not open aiven.pg_security_metrics_file \"%s\": %m", tmppath)));
+        return;
+    }
+    if (fwrite(buf.data, 1, buf.len, file) != (size_t)buf.len)
+    {
+        ereport(LOG,
+                (errcode_for_file_access(),
+                 errmsg("could not write aiven.pg_security_metrics_file \"%s\": %m", tmppath)));
+        FreeFile(file);
+        return;
+    }
+    if (FreeFile(file))
+    {
+        ereport(LOG,
+                (errcode_for_file_access(),
+                 errmsg("could not close aiven.pg_security_metrics_file \"%s\": %m", tmppath)));
+        return;
+    }
+    durable_rename(tmppath, metrics_file, LOG);
+}
+
+/* the counters as a single json object in the server log */
+static void
+log_metrics(uint64 denials_total, StatementMetric *metrics, int num_metrics)
+{
+    StringInfoData buf;
+    int i;
+
+    initStringInfo(&buf);
+    appendStringInfo(&buf, "{\"denials_total\":" UINT64_FORMAT ",\"statement_denials\":{", denials_total);
+    for (i = 0; i < num_metrics; i++)
+    {
+        if (i > 0)
+            appendStringInfoChar(&buf, ',');
+        escape_json(&buf, metrics[i].statement_type);
+        appendStringInfo(&buf, ":" UINT64_FORMAT, metrics[i].denials);
+    }
+    appendStringInfoString(&buf, "}}");
+
+    ereport(LOG,
+            (errmsg("aiven_gatekeeper metrics: %s", buf.data),
+             errhidestmt(true)));
+}
+
+/* background worker publishing the denial counters every aiven.pg_security_metrics_interval,
+ * to aiven.pg_security_metrics_file or to the server log when no file is set.
+ * Doesn't need a database connection, only the shared memory.
+ */
+void
+aiven_gatekeeper_metrics_main(Datum main_arg)
+{
+    StatementMetric metrics[MAX_STATEMENT_METRICS];
+    uint64 denials_total;
+    int num_metrics;
+    int rc;
+
+    pqsignal(SIGHUP, metrics_worker_sighup);
+    BackgroundWorkerUnblockSignals();
+
+    for (;;)
+    {
+        rc = WaitLatch(MyLatch, WL_LATCH_SET | WL_TIMEOUT | WL_POSTMASTER_DEATH, metrics_interval * 1000L, PG_WAIT_EXTENSION);
+        if (rc & WL_POSTMASTER_DEATH)
+            proc_exit(1);
+        ResetLatch(MyLatch);
+        CHECK_FOR_INTERRUPTS();
+
+        if (metrics_worker_got_sighup)
+        {
+            metrics_worker_got_sighup = false;
+            ProcessConfigFile(PGC_SIGHUP);
+            continue;
         }
+        if (!(rc & WL_TIMEOUT))
+            continue;
+
+        LWLockAcquire(gatekeeper_state->lock, LW_SHARED);
+        denials_total = gatekeeper_state->denials_total;
+        num_metrics = gatekeeper_state->num_metrics;
+        memcpy(metrics, gatekeeper_state->metrics, num_metrics * sizeof(StatementMetric));
+        LWLockRelease(gatekeeper_state->lock);
+
+        if (metrics_file != NULL && metrics_file[0] != '\0')
+            write_metrics_file(denials_total, metrics, num_metrics);
+        else
+            log_metrics(denials_total, metrics, num_metrics);
     }
+}
 
-    if (prev_ExecutorStart_hook)
-        prev_ExecutorStart_hook(queryDesc, eflags);
-    else
-        standard_ExecutorStart(queryDesc, eflags);
+static void
+reconcile_worker_sighup(SIGNAL_ARGS)
+{
+    int save_errno = errno;
+
+    reconcile_worker_got_sighup = true;
+    SetLatch(MyLatch);
+
+    errno = save_errno;
+}
+
+/* the superusers in pg_authid, as a comma separated list of names */
+static int
+count_superusers(StringInfo names)
+{
+    Relation rel;
+    SysScanDesc scan;
+    HeapTuple tuple;
+    Form_pg_authid authid;
+    int count = 0;
+
+    rel = table_open(AuthIdRelationId, AccessShareLock);
+    scan = systable_beginscan(rel, InvalidOid, false, NULL, 0, NULL);
+    while (HeapTupleIsValid(tuple = systable_getnext(scan)))
+    {
+        authid = (Form_pg_authid)GETSTRUCT(tuple);
+        if (!authid->rolsuper)
+            continue;
+        if (count > 0)
+            appendStringInfoChar(names, ',');
+        appendStringInfoString(names, NameStr(authid->rolname));
+        count++;
+    }
+    systable_endscan(scan);
+    table_close(rel, AccessShareLock);
+
+    return count;
+}
+
+/* background worker counting the superusers every aiven.pg_security_superuser_reconcile_interval
+ * and logging a change, whichever way the superuser was added or removed. pg_authid is a shared
+ * catalog, so the worker doesn't connect to a database.
+ */
+void
+aiven_gatekeeper_reconcile_main(Datum main_arg)
+{
+    StringInfoData names;
+    char *previous_names = NULL;
+    int previous_count = -1;
+    int count;
+    int rc;
+
+    pqsignal(SIGHUP, reconcile_worker_sighup);
+    BackgroundWorkerUnblockSignals();
+    BackgroundWorkerInitializeConnection(NULL, NULL, 0);
+
+    for (;;)
+    {
+        SetCurrentStatementStartTimestamp();
+        StartTransactionCommand();
+        pgstat_report_activity(STATE_RUNNING, "counting superusers");
+        initStringInfo(&names);
+        count = count_superusers(&names);
+
+        if (previous_count < 0)
+            elog(LOG, "aiven_gatekeeper: %d superusers (%s)", count, names.data);
+        else if (count != previous_count)
+            ereport(WARNING,
+                    (errmsg("aiven_gatekeeper: number of superusers changed from %d to %d", previous_count, count),
+                     errdetail("Superusers before: %s, now: %s.", previous_names, names.data)));
+
+        /* names is released with the transaction, keep a copy across iterations */
+        if (previous_names != NULL)
+            pfree(previous_names);
+        previous_names = MemoryContextStrdup(TopMemoryContext, names.data);
+        previous_count = count;
+        CommitTransactionCommand();
+        pgstat_report_activity(STATE_IDLE, NULL);
+
+        rc = WaitLatch(MyLatch, WL_LATCH_SET | WL_TIMEOUT | WL_POSTMASTER_DEATH, superuser_reconcile_interval * 1000L, PG_WAIT_EXTENSION);
+        if (rc & WL_POSTMASTER_DEATH)
+            proc_exit(1);
+        ResetLatch(MyLatch);
+        CHECK_FOR_INTERRUPTS();
+
+        if (reconcile_worker_got_sighup)
+        {
+            reconcile_worker_got_sighup = false;
+            ProcessConfigFile(PGC_SIGHUP);
+        }
+    }
 }
+
 /*
  * Module Load Callback
  */
@@ -787,13 +5428,473 @@ void _PG_init(void)
                                "Comma-separated list of roles that can be assigned superuser",
                                NULL,
                                &allowed_superuser_roles,
+                               DEFAULT_RESERVED_ROLE, // default to postgres
+                               PGC_SIGHUP,            // only superusers can set, or at postmaster startup
+                               GUC_SUPERUSER_ONLY,    // only show to superuser
+                               reserved_roles_check_hook,
+                               reserved_roles_assign_hook,
+                               NULL);
+
+    // file with additional reserved roles, eg: written by a configuration management sidecar
+    DefineCustomStringVariable("aiven.pg_security_config_file",
+                               "File with additional reserved roles, separated by commas or newlines",
+                               "The file is read again on every configuration reload.",
+                               &reserved_roles_file,
+                               "",                 // default to no file
+                               PGC_SIGHUP,         // only superusers can set, or at postmaster startup
+                               GUC_SUPERUSER_ONLY, // only show to superuser
+                               reserved_roles_file_check_hook,
+                               reserved_roles_file_assign_hook,
+                               NULL);
+
+    // comma-separated list of schemas whose tables get extra protection
+    DefineCustomStringVariable("aiven.pg_security_protected_schemas",
+                               "Comma-separated list of schemas whose tables are protected from destructive statements",
+                               NULL,
+                               &protected_schemas,
+                               "pg_catalog",       // default to the system catalog
+                               PGC_SIGHUP,         // only superusers can set, or at postmaster startup
+                               GUC_SUPERUSER_ONLY, // only show to superuser
+                               allowed_guc_change_allowed_superusers,
+                               NULL,
+                               NULL);
+
+    // comma-separated list of directories COPY TO/FROM FILE is allowed in
+    DefineCustomStringVariable("aiven.pg_security_copy_allowed_paths",
+                               "Comma-separated list of directories where COPY TO/FROM FILE is allowed",
+                               "Relative paths are resolved against the data directory. Not applied in strict mode.",
+                               &copy_allowed_paths,
+                               "",                 // default to no allowed paths
+                               PGC_SIGHUP,         // only superusers can set, or at postmaster startup
+                               GUC_SUPERUSER_ONLY, // only show to superuser
+                               allowed_guc_change_copy_paths,
+                               NULL,
+                               NULL);
+
+    // comma-separated list of schema.table.column entries that can't be copied out
+    DefineCustomStringVariable("aiven.pg_security_sensitive_columns",
+                               "Comma-separated list of schema.table.column entries that COPY TO is not allowed to read",
+                               NULL,
+                               &sensitive_columns,
+                               "",                 // default to no sensitive columns
+                               PGC_SIGHUP,         // only superusers can set, or at postmaster startup
+                               GUC_SUPERUSER_ONLY, // only show to superuser
+                               allowed_guc_change_sensitive_columns,
+                               NULL,
+                               NULL);
+
+    // tell clients when the checks are disabled
+    DefineCustomBoolVariable("aiven.pg_security_notice_when_disabled",
+                             "Send a NOTICE once per session when the security agent is disabled",
+                             NULL,
+                             &notice_when_disabled,
+                             false,              // default to 'off'
+                             PGC_SIGHUP,         // only superusers can set, or at postmaster startup
+                             GUC_SUPERUSER_ONLY, // only show to superuser
+                             allowed_guc_change_check_hook,
+                             NULL,
+                             NULL);
+
+    // comma-separated list of role attributes that can't be set in an elevated context
+    DefineCustomStringVariable("aiven.pg_security_protected_role_attributes",
+                               "Comma-separated list of role attributes that can't be set in an elevated context",
+                               "superuser is always protected",
+                               &protected_role_attributes,
+                               "superuser,replication,bypassrls", // default to the attributes that bypass privilege checks
+                               PGC_SIGHUP,                        // only superusers can set, or at postmaster startup
+                               GUC_SUPERUSER_ONLY,                // only show to superuser
+                               allowed_guc_change_allowed_superusers,
+                               NULL,
+                               NULL);
+
+    // comma-separated list of roles that must keep the LOGIN attribute
+    DefineCustomStringVariable("aiven.pg_security_critical_login_roles",
+                               "Comma-separated list of roles that only the reserved roles can alter to NOLOGIN",
+                               NULL,
+                               &critical_login_roles,
+                               "",                 // default to no roles
+                               PGC_SIGHUP,         // only superusers can set, or at postmaster startup
+                               GUC_SUPERUSER_ONLY, // only show to superuser
+                               allowed_guc_change_allowed_superusers,
+                               NULL,
+                               NULL);
+
+    // comma-separated list of role attributes of the reserved and critical roles only reserved roles can change
+    DefineCustomStringVariable("aiven.pg_security_guarded_role_attributes",
+                               "Comma-separated list of role attributes of the reserved and critical login roles that only reserved roles can change",
+                               "eg: inherit,connection_limit",
+                               &guarded_role_attributes,
+                               "",                 // default to no attributes
+                               PGC_SIGHUP,         // only superusers can set, or at postmaster startup
+                               GUC_SUPERUSER_ONLY, // only show to superuser
+                               allowed_guc_change_allowed_superusers,
+                               NULL,
+                               NULL);
+
+    // privileged statements need a justification
+    DefineCustomBoolVariable("aiven.pg_security_require_change_reason",
+                             "Require aiven.pg_security_change_reason to be set for superuser grants and extension installs",
+                             NULL,
+                             &require_change_reason,
+                             false,              // default to 'off'
+                             PGC_SIGHUP,         // only superusers can set, or at postmaster startup
+                             GUC_SUPERUSER_ONLY, // only show to superuser
+                             allowed_guc_change_check_hook,
+                             NULL,
+                             NULL);
+
+    // log the privileged statements that were allowed, not only the denials
+    DefineCustomBoolVariable("aiven.pg_security_audit_allowed",
+                             "Write an audit record for allowed superuser grants and extension installs",
+                             NULL,
+                             &audit_allowed,
+                             false,              // default to 'off'
+                             PGC_SIGHUP,         // only superusers can set, or at postmaster startup
+                             GUC_SUPERUSER_ONLY, // only show to superuser
+                             allowed_guc_change_check_hook,
+                             NULL,
+                             NULL);
+
+    // the justification for the privileged statements of this session, included in the audit records
+    DefineCustomStringVariable("aiven.pg_security_change_reason",
+                               "Reason for the privileged changes made in this session, included in the audit records",
+                               NULL,
+                               &change_reason,
+                               "",          // default to no reason
+                               PGC_USERSET, // set by the session making the change
+                               0,
+                               NULL,
+                               NULL,
+                               NULL);
+
+    // trace context of the application, included in the audit records
+    DefineCustomStringVariable("aiven.pg_security_trace_id",
+                               "Trace id of the application, included in the audit records",
+                               NULL,
+                               &trace_id,
+                               "",          // default to no trace id
+                               PGC_USERSET, // set by the application
+                               0,
+                               trace_id_check_hook,
+                               NULL,
+                               NULL);
+
+    // comma-separated list of extensions that can be created, see extension_policy_allows
+    DefineCustomStringVariable("aiven.pg_security_extension_policy",
+                               "Comma-separated list of extensions that can be created, !name excludes one, * allows all others",
+                               "An empty list allows all extensions, file_fdw is always blocked",
+                               &extension_policy,
+                               "",                 // default to all extensions
+                               PGC_SIGHUP,         // only superusers can set, or at postmaster startup
+                               GUC_SUPERUSER_ONLY, // only show to superuser
+                               allowed_guc_change_allowed_superusers,
+                               NULL,
+                               NULL);
+
+    // deny COPY TO/FROM PROGRAM also in a monitor only build
+    DefineCustomBoolVariable("aiven.pg_security_copy_program_hard_block",
+                             "Deny COPY TO/FROM PROGRAM also in a monitor only build",
+                             NULL,
+                             &copy_program_hard_block,
+                             false,              // default to 'off'
+                             PGC_SIGHUP,         // only superusers can set, or at postmaster startup
+                             GUC_SUPERUSER_ONLY, // only show to superuser
+                             allowed_guc_change_check_hook,
+                             NULL,
+                             NULL);
+
+    // block superuser owned SECURITY DEFINER functions
+    DefineCustomBoolVariable("aiven.pg_security_block_security_definer",
+                             "Block creating SECURITY DEFINER functions as superuser, unless the creator is a reserved role",
+                             NULL,
+                             &block_security_definer,
+                             false,              // default to 'off'
+                             PGC_SIGHUP,         // only superusers can set, or at postmaster startup
+                             GUC_SUPERUSER_ONLY, // only show to superuser
+                             allowed_guc_change_check_hook,
+                             NULL,
+                             NULL);
+
+#if GATEKEEPER_EXECUTOR_HOOK
+    // comma-separated list of roles that get a capped statement_timeout
+    DefineCustomStringVariable("aiven.pg_security_timeout_roles",
+                               "Comma-separated list of roles that get aiven.pg_security_forced_statement_timeout applied",
+                               NULL,
+                               &timeout_roles,
+                               "",                 // default to no roles
+                               PGC_SIGHUP,         // only superusers can set, or at postmaster startup
+                               GUC_SUPERUSER_ONLY, // only show to superuser
+                               allowed_guc_change_allowed_superusers,
+                               NULL,
+                               NULL);
+
+    // maximum statement_timeout for the timeout roles
+    DefineCustomIntVariable("aiven.pg_security_forced_statement_timeout",
+                            "Maximum statement_timeout for the roles in aiven.pg_security_timeout_roles",
+                            "0 disables the cap",
+                            &forced_statement_timeout,
+                            0,                                // default to disabled
+                            0,
+                            INT_MAX,
+                            PGC_SIGHUP,                       // only superusers can set, or at postmaster startup
+                            GUC_SUPERUSER_ONLY | GUC_UNIT_MS, // only show to superuser
+                            allowed_guc_change_int_check_hook,
+                            NULL,
+                            NULL);
+#endif
+
+    // only allow encrypted passwords, and reserved role passwords set by reserved roles
+    DefineCustomBoolVariable("aiven.pg_security_enforce_password_policy",
+                             "Block plain text passwords, and setting the password of a reserved role by other roles",
+                             NULL,
+                             &enforce_password_policy,
+                             false,              // default to 'off'
+                             PGC_SIGHUP,         // only superusers can set, or at postmaster startup
+                             GUC_SUPERUSER_ONLY, // only show to superuser
+                             allowed_guc_change_check_hook,
+                             NULL,
+                             NULL);
+
+    // categories of checks the reserved roles can bypass outside of strict mode
+    DefineCustomStringVariable("aiven.pg_security_reserved_bypass",
+                               "Comma-separated list of check categories (extension, function) the reserved roles can bypass outside of strict mode",
+                               NULL,
+                               &reserved_bypass,
+                               "",                 // default to no bypass
+                               PGC_SIGHUP,         // only superusers can set, or at postmaster startup
+                               GUC_SUPERUSER_ONLY, // only show to superuser
+                               allowed_guc_change_allowed_superusers,
+                               NULL,
+                               NULL);
+
+    // comma-separated list of roles exempt from all utility statement checks
+    DefineCustomStringVariable("aiven.pg_security_bypass_roles",
+                               "Comma-separated list of roles exempt from all utility statement checks outside of strict mode",
+                               NULL,
+                               &bypass_roles,
+                               "",                 // default to no roles
+                               PGC_SIGHUP,         // only superusers can set, or at postmaster startup
+                               GUC_SUPERUSER_ONLY, // only show to superuser
+                               allowed_guc_change_allowed_superusers,
+                               NULL,
+                               NULL);
+
+    // regular expression the names of roles created by non-reserved roles have to match
+    DefineCustomStringVariable("aiven.pg_security_role_name_pattern",
+                               "Regular expression the names of roles created by non-reserved roles have to match",
+                               "Matched against the whole name, an empty pattern allows all names",
+                               &role_name_pattern,
+                               "",                 // default to no policy
+                               PGC_SIGHUP,         // only superusers can set, or at postmaster startup
+                               GUC_SUPERUSER_ONLY, // only show to superuser
+                               allowed_guc_change_role_name_pattern,
+                               NULL,
+                               NULL);
+
+    // number of denials for the same role before an anomaly is logged
+    DefineCustomIntVariable("aiven.pg_security_anomaly_threshold",
+                            "Number of denials for the same role within the anomaly window that is logged as an anomaly",
+                            "0 disables anomaly detection",
+                            &anomaly_threshold,
+                            5,                  // default to 5 denials
+                            0,
+                            INT_MAX,
+                            PGC_SIGHUP,         // only superusers can set, or at postmaster startup
+                            GUC_SUPERUSER_ONLY, // only show to superuser
+                            allowed_guc_change_int_check_hook,
+                            NULL,
+                            NULL);
+
+    // options accepted in a single CREATE ROLE or ALTER ROLE
+    DefineCustomIntVariable("aiven.pg_security_max_role_options",
+                            "Maximum number of options in a single CREATE ROLE or ALTER ROLE statement",
+                            NULL,
+                            &max_role_options,
+                            100,                // default to 100 options
+                            1,
+                            INT_MAX,
+                            PGC_SIGHUP,         // only superusers can set, or at postmaster startup
+                            GUC_SUPERUSER_ONLY, // only show to superuser
+                            allowed_guc_change_int_check_hook,
+                            NULL,
+                            NULL);
+
+    // window in which the denials are counted
+    DefineCustomIntVariable("aiven.pg_security_anomaly_window",
+                            "Time window in which denials for the same role are counted",
+                            NULL,
+                            &anomaly_window,
+                            60,                              // default to one minute
+                            1,
+                            INT_MAX / 1000,
+                            PGC_SIGHUP,                      // only superusers can set, or at postmaster startup
+                            GUC_SUPERUSER_ONLY | GUC_UNIT_S, // only show to superuser
+                            allowed_guc_change_int_check_hook,
+                            NULL,
+                            NULL);
+
+    // send denials as NOTIFY from a background worker
+    DefineCustomBoolVariable("aiven.pg_security_denial_notify",
+                             "Send denied statements as NOTIFY on aiven.pg_security_denial_notify_channel",
+                             "Requires loading through shared_preload_libraries",
+                             &denial_notify_enabled,
+                             false,              // default to 'off'
+                             PGC_POSTMASTER,     // only at postmaster startup
+                             GUC_SUPERUSER_ONLY, // only show to superuser
+                             allowed_guc_change_check_hook,
+                             NULL,
+                             NULL);
+
+    // channel the denials are sent on
+    DefineCustomStringVariable("aiven.pg_security_denial_notify_channel",
+                               "NOTIFY channel the denied statements are sent on",
+                               NULL,
+                               &denial_notify_channel,
+                               "aiven_gatekeeper_denials", // default channel
+                               PGC_SIGHUP,                 // only superusers can set, or at postmaster startup
+                               GUC_SUPERUSER_ONLY,         // only show to superuser
+                               allowed_guc_change_notify_channel,
+                               NULL,
+                               NULL);
+
+    // database the notify worker connects to, listeners need to be connected to the same database
+    DefineCustomStringVariable("aiven.pg_security_denial_notify_database",
+                               "Database the denial notifications are sent in",
+                               NULL,
+                               &denial_notify_database,
                                "postgres",         // default to postgres
                                PGC_POSTMASTER,     // only at postmaster startup
                                GUC_SUPERUSER_ONLY, // only show to superuser
-                               allowed_guc_change_allowed_superusers,
+                               allowed_guc_change_notify_database,
+                               NULL,
+                               NULL);
+
+    // publish the denial counters from a background worker
+    DefineCustomBoolVariable("aiven.pg_security_metrics",
+                             "Publish the denial counters periodically from a background worker",
+                             "Requires loading through shared_preload_libraries",
+                             &metrics_enabled,
+                             false,              // default to 'off'
+                             PGC_POSTMASTER,     // only at postmaster startup
+                             GUC_SUPERUSER_ONLY, // only show to superuser
+                             allowed_guc_change_check_hook,
+                             NULL,
+                             NULL);
+
+    // how often the counters are published
+    DefineCustomIntVariable("aiven.pg_security_metrics_interval",
+                            "Interval in which the denial counters are published",
+                            NULL,
+                            &metrics_interval,
+                            60,                              // default to one minute
+                            1,
+                            INT_MAX / 1000,
+                            PGC_SIGHUP,                      // only superusers can set, or at postmaster startup
+                            GUC_SUPERUSER_ONLY | GUC_UNIT_S, // only show to superuser
+                            allowed_guc_change_int_check_hook,
+                            NULL,
+                            NULL);
+
+    // file the counters are written to in the prometheus text format, the server log when empty
+    DefineCustomStringVariable("aiven.pg_security_metrics_file",
+                               "File the denial counters are written to, they are logged when empty",
+                               NULL,
+                               &metrics_file,
+                               "",                 // default to the server log
+                               PGC_SIGHUP,         // only superusers can set, or at postmaster startup
+                               GUC_SUPERUSER_ONLY, // only show to superuser
+                               allowed_guc_change_metrics_file,
+                               NULL,
+                               NULL);
+
+    // comma-separated list of additional builtin functions checked by the object access hook
+    DefineCustomStringVariable("aiven.pg_security_reserved_functions",
+                               "Comma-separated list of additional builtin functions only superusers can call",
+                               "Internal names of the builtin functions, eg: pg_ls_dir",
+                               &extra_reserved_functions,
+                               "",                 // default to only the predefined functions
+                               PGC_POSTMASTER,     // only at postmaster startup
+                               GUC_SUPERUSER_ONLY, // only show to superuser
+                               allowed_guc_change_reserved_functions,
+                               NULL,
+                               NULL);
+
+    // count the superusers periodically from a background worker
+    DefineCustomBoolVariable("aiven.pg_security_superuser_reconcile",
+                             "Count the superusers periodically from a background worker and log changes",
+                             "Requires loading through shared_preload_libraries",
+                             &superuser_reconcile_enabled,
+                             false,              // default to 'off'
+                             PGC_POSTMASTER,     // only at postmaster startup
+                             GUC_SUPERUSER_ONLY, // only show to superuser
+                             allowed_guc_change_check_hook,
+                             NULL,
+                             NULL);
+
+    // how often the superusers are counted
+    DefineCustomIntVariable("aiven.pg_security_superuser_reconcile_interval",
+                            "Interval in which the superusers are counted",
+                            NULL,
+                            &superuser_reconcile_interval,
+                            300,                             // default to five minutes
+                            1,
+                            INT_MAX / 1000,
+                            PGC_SIGHUP,                      // only superusers can set, or at postmaster startup
+                            GUC_SUPERUSER_ONLY | GUC_UNIT_S, // only show to superuser
+                            allowed_guc_change_int_check_hook,
+                            NULL,
+                            NULL);
+
+    // format of the audit records for denied statements
+    DefineCustomEnumVariable("aiven.pg_security_audit_format",
+                             "Format of the audit records written for denied statements",
+                             "text appends the audit fields to the log detail, json writes a single line json object",
+                             &pg_security_audit_format,
+                             AUDIT_FORMAT_TEXT,  // default to text
+                             audit_format_options,
+                             PGC_SIGHUP,         // only superusers can set, or at postmaster startup
+                             GUC_SUPERUSER_ONLY, // only show to superuser
+                             allowed_guc_change_int_check_hook,
+                             NULL,
+                             NULL);
+
+    // what to do with utility statement types that have no check
+    DefineCustomEnumVariable("aiven.pg_security_default_deny",
+                             "Log or deny utility statement types without a gatekeeper check",
+                             "off allows them, log writes an audit record, on denies them for non-reserved roles",
+                             &default_deny,
+                             DEFAULT_DENY_OFF,   // default to off
+                             default_deny_options,
+                             PGC_SIGHUP,         // only superusers can set, or at postmaster startup
+                             GUC_SUPERUSER_ONLY, // only show to superuser
+                             allowed_guc_change_int_check_hook,
+                             NULL,
+                             NULL);
+
+    // secret for aiven_gatekeeper_break_glass(), break glass is disabled when empty
+    DefineCustomStringVariable("aiven.pg_security_break_glass_secret",
+                               "Secret that lets a reserved role lift strict mode for its own session",
+                               NULL,
+                               &break_glass_secret,
+                               "",                                   // default to disabled
+                               PGC_POSTMASTER,                       // only at postmaster startup
+                               GUC_SUPERUSER_ONLY | GUC_NO_SHOW_ALL, // only show to superuser, and not in SHOW ALL
+                               NULL,
                                NULL,
                                NULL);
 
+    // strict mode that can be enabled with a reload, it can't disable the startup setting
+    DefineCustomBoolVariable("aiven.pg_security_agent_strict_runtime",
+                             "Enable strict mode without a restart, in addition to aiven.pg_security_agent_strict",
+                             NULL,
+                             &pg_security_agent_strict_runtime,
+                             false,              // default to 'off'
+                             PGC_SIGHUP,         // only superusers can set, or at postmaster startup
+                             GUC_SUPERUSER_ONLY, // only show to superuser
+                             strict_runtime_check_hook,
+                             NULL,
+                             NULL);
+
     // allow toggling of the security agent
     // this variable definition should always be last, otherwise further defines
     // stop working because the agent has defaulted to strict = on
@@ -808,6 +5909,77 @@ void _PG_init(void)
                              NULL,
                              NULL);
 
+    /* the reserved roles can't be changed once strict mode is on, make an empty list visible */
+    if (pg_security_agent_strict)
+    {
+        List *configured_roles = NIL;
+
+        split_identifier_list(reserved_roles_setting(), &configured_roles, NULL);
+        if (configured_roles == NIL)
+            elog(WARNING, "aiven.pg_security_agent_reserved_roles is empty in strict mode, falling back to \"%s\"", DEFAULT_RESERVED_ROLE);
+        list_free(configured_roles);
+    }
+
+    register_default_checks();
+    CacheRegisterSyscacheCallback(AUTHOID, reserved_status_invalidate, (Datum)0);
+
+    /* shared memory and the notify worker are only possible when preloaded */
+    if (process_shared_preload_libraries_in_progress)
+    {
+#if PG15_GTE
+        prev_shmem_request_hook = shmem_request_hook;
+        shmem_request_hook = gatekeeper_shmem_request;
+#else
+        gatekeeper_shmem_request();
+#endif
+        prev_shmem_startup_hook = shmem_startup_hook;
+        shmem_startup_hook = gatekeeper_shmem_startup;
+
+        if (denial_notify_enabled)
+        {
+            BackgroundWorker worker;
+
+            memset(&worker, 0, sizeof(worker));
+            worker.bgw_flags = BGWORKER_SHMEM_ACCESS | BGWORKER_BACKEND_DATABASE_CONNECTION;
+            worker.bgw_start_time = BgWorkerStart_RecoveryFinished;
+            worker.bgw_restart_time = 10;
+            snprintf(worker.bgw_library_name, BGW_MAXLEN, "aiven_gatekeeper");
+            snprintf(worker.bgw_function_name, BGW_MAXLEN, "aiven_gatekeeper_notify_main");
+            snprintf(worker.bgw_name, BGW_MAXLEN, "aiven_gatekeeper notify worker");
+            snprintf(worker.bgw_type, BGW_MAXLEN, "aiven_gatekeeper notify worker");
+            RegisterBackgroundWorker(&worker);
+        }
+
+        if (metrics_enabled)
+        {
+            BackgroundWorker worker;
+
+            memset(&worker, 0, sizeof(worker));
+            worker.bgw_flags = BGWORKER_SHMEM_ACCESS;
+            worker.bgw_start_time = BgWorkerStart_PostmasterStart;
+            worker.bgw_restart_time = 10;
+            snprintf(worker.bgw_library_name, BGW_MAXLEN, "aiven_gatekeeper");
+            snprintf(worker.bgw_function_name, BGW_MAXLEN, "aiven_gatekeeper_metrics_main");
+            snprintf(worker.bgw_name, BGW_MAXLEN, "aiven_gatekeeper metrics worker");
+            snprintf(worker.bgw_type, BGW_MAXLEN, "aiven_gatekeeper metrics worker");
+            RegisterBackgroundWorker(&worker);
+        }
+
+        if (superuser_reconcile_enabled)
+        {
+            BackgroundWorker worker;
+
+            memset(&worker, 0, sizeof(worker));
+            worker.bgw_flags = BGWORKER_SHMEM_ACCESS | BGWORKER_BACKEND_DATABASE_CONNECTION;
+            worker.bgw_start_time = BgWorkerStart_RecoveryFinished;
+            worker.bgw_restart_time = 10;
+            snprintf(worker.bgw_library_name, BGW_MAXLEN, "aiven_gatekeeper");
+            snprintf(worker.bgw_function_name, BGW_MAXLEN, "aiven_gatekeeper_reconcile_main");
+            snprintf(worker.bgw_name, BGW_MAXLEN, "aiven_gatekeeper reconcile worker");
+            snprintf(worker.bgw_type, BGW_MAXLEN, "aiven_gatekeeper reconcile worker");
+            RegisterBackgroundWorker(&worker);
+        }
+    }
 
     if (set_reserved_oids())
     {
@@ -815,11 +5987,17 @@ void _PG_init(void)
         prev_ProcessUtility = ProcessUtility_hook;
         ProcessUtility_hook = gatekeeper_checks;
 
+#if GATEKEEPER_OBJECT_ACCESS_HOOK
         next_object_access_hook = object_access_hook;
         object_access_hook = gatekeeper_oa_hook;
+#endif
 
+#if GATEKEEPER_EXECUTOR_HOOK
         prev_ExecutorStart_hook = ExecutorStart_hook;
         ExecutorStart_hook = pg_proc_guard_checks;
+        prev_ExecutorRun_hook = ExecutorRun_hook;
+        ExecutorRun_hook = forced_timeout_executor_run;
+#endif
     }
     else
     {
@@ -832,12 +6010,32 @@ void _PG_init(void)
  */
 void _PG_fini(void)
 {
+    int i;
+
     /* free malloc(s) */
     if (reserved_func_oids != NULL)
         free(reserved_func_oids);
+    if (reserved_func_list != NULL)
+    {
+        for (i = NUM_RESERVED_FUNCS; i < num_reserved_funcs; i++)
+            free((void *)reserved_func_list[i]);
+        free((void *)reserved_func_list);
+    }
 
     /* Uninstall hooks. */
     ProcessUtility_hook = prev_ProcessUtility;
+    /* the shared memory hooks are only installed when preloaded */
+    if (shmem_startup_hook == gatekeeper_shmem_startup)
+        shmem_startup_hook = prev_shmem_startup_hook;
+#if PG15_GTE
+    if (shmem_request_hook == gatekeeper_shmem_request)
+        shmem_request_hook = prev_shmem_request_hook;
+#endif
+#if GATEKEEPER_OBJECT_ACCESS_HOOK
     object_access_hook = next_object_access_hook;
+#endif
+#if GATEKEEPER_EXECUTOR_HOOK
     ExecutorStart_hook = prev_ExecutorStart_hook;
+    ExecutorRun_hook = prev_ExecutorRun_hook;
+#endif
 }
@@ -11,11 +11,36 @@
  * my attempt at using #ifdef directly in aiven_gatekeeper.c
  */
 #define PG11_GTE (PG_VERSION_NUM >= 110000)
+#define PG12_GTE (PG_VERSION_NUM >= 120000)
 #define PG13_GTE (PG_VERSION_NUM >= 130000)
 #define PG14_GTE (PG_VERSION_NUM >= 140000)
+#define PG15_GTE (PG_VERSION_NUM >= 150000)
 #define PG16_GTE (PG_VERSION_NUM >= 160000)
 #define PG17_GTE (PG_VERSION_NUM >= 170000)
 
+/* Build time toggles for the optional hooks, both default to on.
+ * The utility hook is always installed.
+ */
+#ifndef GATEKEEPER_EXECUTOR_HOOK
+#define GATEKEEPER_EXECUTOR_HOOK 1
+#endif
+#ifndef GATEKEEPER_OBJECT_ACCESS_HOOK
+#define GATEKEEPER_OBJECT_ACCESS_HOOK 1
+#endif
+
+/* Build time toggle for a monitor only variant, defaults to off.
+ * All checks still run, but a denial is reported as a WARNING and the statement continues.
+ */
+#ifndef GATEKEEPER_MONITOR_ONLY
+#define GATEKEEPER_MONITOR_ONLY 0
+#endif
+
+/* Build time toggle for the test only API below, defaults to off.
+ * Never enable it in production builds.
+ */
+#ifndef GATEKEEPER_TEST_API
+#define GATEKEEPER_TEST_API 0
+#endif
 
 /* The process_utility_hook function changed in PG13 and again in PG14
  * versions from introduction (PG9) through PG12 have the same 7 argument structure
@@ -49,3 +74,21 @@
 
 #endif
 
+/* Additional utility statement checks can be registered by other libraries.
+ * A check receives the utility statement and raises an ERROR to deny it.
+ */
+#define GATEKEEPER_MAX_CHECKS 64
+
+typedef void (*gatekeeper_check_fn)(Node *stmt);
+
+extern PGDLLEXPORT void aiven_gatekeeper_register_check(NodeTag tag, gatekeeper_check_fn check);
+
+#if GATEKEEPER_TEST_API
+/* Build minimal statement nodes in the current memory context and run them through the
+ * registered checks without executing them, eg: for regression tests of the node walking
+ * in the checks. A denial raises an ERROR, must be called inside a transaction.
+ */
+extern PGDLLEXPORT Node *aiven_gatekeeper_test_create_role_stmt(const char *rolename, const char *option, const char *value);
+extern PGDLLEXPORT Node *aiven_gatekeeper_test_copy_stmt(const char *relname, const char *filename, bool is_from, bool is_program);
+extern PGDLLEXPORT void aiven_gatekeeper_test_run_checks(Node *stmt, ProcessUtilityContext context);
+#endif
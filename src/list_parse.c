@@ -0,0 +1,76 @@
+/* -------------------------------------------------------------------------
+ *
+ * list_parse.c
+ *
+ * Copyright (c) 2022 Aiven, Helsinki, Finland. https://aiven.io/
+ *
+ * IDENTIFICATION
+ *		src/list_parse.c
+ *
+ * -------------------------------------------------------------------------
+ */
+#include "postgres.h"
+
+#include "parser/scansup.h"
+
+#include "list_parse.h"
+
+/* copy the comma-separated list to out without the empty entries, commas inside
+ * quotes are part of the name. out needs room for strlen(value) + 1 bytes.
+ * This only looks at the bytes it is given and doesn't allocate, so any input,
+ * including unterminated quotes, ends at the terminating NUL.
+ */
+void
+gatekeeper_strip_empty_list_entries(const char *value, char *out)
+{
+    const char *token_start = value;
+    const char *start;
+    const char *p;
+    char *o = out;
+    bool in_quotes = false;
+
+    for (p = value;; p++)
+    {
+        if (*p == '"')
+            in_quotes = !in_quotes;
+        else if (*p == '\0' || (*p == ',' && !in_quotes))
+        {
+            start = token_start;
+            while (start < p && scanner_isspace(*start))
+                start++;
+            if (start < p)
+            {
+                if (o != out)
+                    *o++ = ',';
+                memcpy(o, token_start, p - token_start);
+                o += p - token_start;
+            }
+            if (*p == '\0')
+                break;
+            token_start = p + 1;
+        }
+    }
+    *o = '\0';
+}
+
+/* split a schema.table.column entry of aiven.pg_security_sensitive_columns,
+ * names containing a dot can't be used
+ */
+bool
+gatekeeper_parse_sensitive_column(const char *entry, char **schema, char **table, char **column)
+{
+    const char *first;
+    const char *second;
+
+    first = strchr(entry, '.');
+    if (first == NULL || first == entry)
+        return false;
+    second = strchr(first + 1, '.');
+    if (second == NULL || second == first + 1 || second[1] == '\0' || strchr(second + 1, '.') != NULL)
+        return false;
+
+    *schema = pnstrdup(entry, first - entry);
+    *table = pnstrdup(first + 1, second - first - 1);
+    *column = pstrdup(second + 1);
+    return true;
+}
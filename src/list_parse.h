@@ -0,0 +1,21 @@
+/* -------------------------------------------------------------------------
+ *
+ * list_parse.h
+ *
+ * Copyright (c) 2022 Aiven, Helsinki, Finland. https://aiven.io/
+ *
+ * IDENTIFICATION
+ *		src/list_parse.h
+ *
+ * -------------------------------------------------------------------------
+ */
+#ifndef AIVEN_GATEKEEPER_LIST_PARSE_H
+#define AIVEN_GATEKEEPER_LIST_PARSE_H
+
+/* The parsers of the list settings that only work on the string they are given, without
+ * catalog access or any other server state, so the fuzz driver in fuzz/ can link them.
+ */
+extern void gatekeeper_strip_empty_list_entries(const char *value, char *out);
+extern bool gatekeeper_parse_sensitive_column(const char *entry, char **schema, char **table, char **column);
+
+#endif
@@ -0,0 +1,16 @@
+-- the anomaly counters are shared, reconnecting doesn't start the count over
+CREATE ROLE gk_anomaly;
+ALTER SYSTEM SET aiven.pg_security_anomaly_threshold = 2;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+SET SESSION AUTHORIZATION gk_anomaly;
+COPY (SELECT 1) TO PROGRAM 'true';
+\c
+SET SESSION AUTHORIZATION gk_anomaly;
+COPY (SELECT 1) TO PROGRAM 'true';
+SET SESSION AUTHORIZATION gk_admin;
+ALTER SYSTEM RESET aiven.pg_security_anomaly_threshold;
+RESET SESSION AUTHORIZATION;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+DROP ROLE gk_anomaly;
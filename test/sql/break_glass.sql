@@ -0,0 +1,29 @@
+-- break glass lifts strict mode for the session of a reserved role with the right token
+ALTER SYSTEM SET aiven.pg_security_agent_strict_runtime = on;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+-- not a reserved role
+SELECT aiven_gatekeeper_break_glass('gk_break_glass_regress_secret');
+SET SESSION AUTHORIZATION gk_admin;
+-- wrong tokens
+SELECT aiven_gatekeeper_break_glass('gk_break_glass_wrong_secret');
+SELECT aiven_gatekeeper_break_glass('gk_break_glass_regress_secre');
+SELECT aiven_gatekeeper_break_glass('');
+COPY (SELECT 1) TO '/tmp/gk_break_glass.out';
+-- the audit record has the pid of the session
+SET client_min_messages = error;
+SELECT aiven_gatekeeper_break_glass('gk_break_glass_regress_secret');
+RESET client_min_messages;
+COPY (SELECT 1) TO '/tmp/gk_break_glass.out';
+RESET SESSION AUTHORIZATION;
+-- other sessions are still in strict mode
+\c
+SET SESSION AUTHORIZATION gk_admin;
+COPY (SELECT 1) TO '/tmp/gk_break_glass.out';
+ALTER SYSTEM SET aiven.pg_security_agent_strict_runtime = off;
+SELECT aiven_gatekeeper_reload();
+RESET SESSION AUTHORIZATION;
+SELECT pg_sleep(1);
+SET SESSION AUTHORIZATION gk_admin;
+ALTER SYSTEM RESET aiven.pg_security_agent_strict_runtime;
+RESET SESSION AUTHORIZATION;
@@ -0,0 +1,21 @@
+-- the bypass roles skip all utility statement checks, eg: the password policy
+CREATE ROLE gk_bypass CREATEROLE;
+ALTER SYSTEM SET aiven.pg_security_bypass_roles = 'gk_bypass';
+ALTER SYSTEM SET aiven.pg_security_enforce_password_policy = on;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+CREATE ROLE gk_bypassed PASSWORD 'plain';
+SET SESSION AUTHORIZATION gk_bypass;
+CREATE ROLE gk_bypassed PASSWORD 'plain';
+RESET SESSION AUTHORIZATION;
+-- only as the session role, SET ROLE doesn't exempt other roles
+SET ROLE gk_bypass;
+CREATE ROLE gk_bypassed_role PASSWORD 'plain';
+RESET ROLE;
+SET SESSION AUTHORIZATION gk_admin;
+ALTER SYSTEM RESET aiven.pg_security_bypass_roles;
+ALTER SYSTEM RESET aiven.pg_security_enforce_password_policy;
+RESET SESSION AUTHORIZATION;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+DROP ROLE gk_bypassed, gk_bypass;
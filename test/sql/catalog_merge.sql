@@ -0,0 +1,11 @@
+-- MERGE into the catalogs, PG15 and later
+SELECT current_setting('server_version_num')::int >= 150000 AS merge_supported \gset
+\if :merge_supported
+CREATE ROLE gk_target;
+MERGE INTO pg_authid a USING (SELECT 'gk_target'::name AS rolname) s ON a.rolname = s.rolname
+    WHEN MATCHED THEN UPDATE SET rolsuper = true;
+MERGE INTO pg_proc p USING (SELECT 'gk_no_such_function'::name AS proname) s ON p.proname = s.proname
+    WHEN MATCHED THEN UPDATE SET prosecdef = true;
+SELECT rolsuper FROM pg_authid WHERE rolname = 'gk_target';
+DROP ROLE gk_target;
+\endif
@@ -0,0 +1,22 @@
+-- direct catalog writes, the bootstrap superuser running the tests is not a reserved role
+CREATE ROLE gk_target;
+-- executor checks
+UPDATE pg_authid SET rolsuper = true WHERE rolname = 'gk_target';
+INSERT INTO pg_auth_members (roleid, member, grantor, admin_option) SELECT 10, oid, 10, false FROM pg_roles WHERE rolname = 'gk_target';
+DELETE FROM pg_authid WHERE rolname = 'gk_target';
+UPDATE pg_proc SET prosecdef = true WHERE proname = 'gk_no_such_function';
+-- columns outside proowner, prolang, prosecdef, proacl can still be changed
+UPDATE pg_proc SET procost = 1 WHERE proname = 'gk_no_such_function';
+-- COPY FROM doesn't go through the executor checks
+COPY pg_authid (rolname) FROM STDIN;
+COPY pg_auth_members (roleid, member) FROM STDIN;
+COPY pg_proc (proname) FROM STDIN;
+-- reading is not affected
+COPY (SELECT rolname FROM pg_authid WHERE rolname = 'gk_target') TO STDOUT;
+SELECT rolsuper FROM pg_authid WHERE rolname = 'gk_target';
+-- reserved roles can modify the catalogs
+SET SESSION AUTHORIZATION gk_admin;
+UPDATE pg_authid SET rolconnlimit = 1 WHERE rolname = 'gk_target';
+RESET SESSION AUTHORIZATION;
+SELECT rolconnlimit FROM pg_authid WHERE rolname = 'gk_target';
+DROP ROLE gk_target;
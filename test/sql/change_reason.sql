@@ -0,0 +1,18 @@
+-- superuser grants and extension installs need a change reason, also for the reserved roles
+ALTER SYSTEM SET aiven.pg_security_require_change_reason = on;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+SET SESSION AUTHORIZATION gk_admin;
+ALTER ROLE gk_admin SUPERUSER;
+CREATE EXTENSION IF NOT EXISTS plpgsql;
+SET aiven.pg_security_change_reason = 'restore the admin role, ticket 42';
+ALTER ROLE gk_admin SUPERUSER;
+CREATE EXTENSION IF NOT EXISTS plpgsql;
+-- an empty reason is no reason
+SET aiven.pg_security_change_reason = '';
+CREATE EXTENSION IF NOT EXISTS plpgsql;
+RESET aiven.pg_security_change_reason;
+ALTER SYSTEM RESET aiven.pg_security_require_change_reason;
+RESET SESSION AUTHORIZATION;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
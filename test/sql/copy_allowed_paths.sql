@@ -0,0 +1,33 @@
+-- strict mode still allows COPY TO/FROM FILE in the allowed directories, for roles with the server file permissions
+CREATE TABLE gk_allowed (id int);
+SET SESSION AUTHORIZATION gk_admin;
+CREATE ROLE gk_copy_user;
+GRANT pg_read_server_files TO gk_copy_user;
+GRANT INSERT ON gk_allowed TO gk_copy_user;
+RESET SESSION AUTHORIZATION;
+ALTER SYSTEM SET aiven.pg_security_copy_allowed_paths = '/tmp';
+ALTER SYSTEM SET aiven.pg_security_agent_strict_runtime = on;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+COPY (SELECT 1) TO '/tmp/gk_allowed_path.out';
+COPY gk_allowed FROM '/tmp/gk_allowed_path.out';
+COPY (SELECT 1) TO '/var/tmp/gk_allowed_path.out';
+COPY (SELECT 1) TO '/tmp/../var/tmp/gk_allowed_path.out';
+-- pg_read_server_files only allows reading
+SET SESSION AUTHORIZATION gk_copy_user;
+COPY gk_allowed FROM '/tmp/gk_allowed_path.out';
+COPY (SELECT 1) TO '/tmp/gk_allowed_path.out';
+RESET SESSION AUTHORIZATION;
+SELECT count(*) FROM gk_allowed;
+-- back to normal
+SET SESSION AUTHORIZATION gk_admin;
+ALTER SYSTEM SET aiven.pg_security_agent_strict_runtime = off;
+SELECT aiven_gatekeeper_reload();
+ALTER SYSTEM RESET aiven.pg_security_agent_strict_runtime;
+ALTER SYSTEM RESET aiven.pg_security_copy_allowed_paths;
+RESET SESSION AUTHORIZATION;
+SELECT pg_sleep(1);
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+DROP TABLE gk_allowed;
+DROP ROLE gk_copy_user;
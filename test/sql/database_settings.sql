@@ -0,0 +1,12 @@
+-- the settings that load code or configure the gatekeeper can only be set on a database by the reserved roles
+CREATE DATABASE gk_settings;
+ALTER DATABASE gk_settings SET session_preload_libraries = 'auto_explain';
+ALTER DATABASE gk_settings SET aiven.pg_security_agent_strict_runtime = off;
+ALTER DATABASE gk_settings SET work_mem = '8MB';
+SET SESSION AUTHORIZATION gk_admin;
+ALTER DATABASE gk_settings SET session_preload_libraries = 'auto_explain';
+RESET SESSION AUTHORIZATION;
+SELECT setting FROM pg_db_role_setting s JOIN pg_database d ON d.oid = s.setdatabase, unnest(s.setconfig) AS setting WHERE d.datname = 'gk_settings' ORDER BY setting COLLATE "C";
+-- resetting only removes the stored value
+ALTER DATABASE gk_settings RESET session_preload_libraries;
+DROP DATABASE gk_settings;
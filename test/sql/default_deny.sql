@@ -0,0 +1,22 @@
+-- drops are unchecked statements for the default deny, except for dropping the extension
+CREATE TABLE gk_drop_table (id int);
+CREATE FUNCTION gk_drop_function() RETURNS int LANGUAGE sql AS 'SELECT 1';
+ALTER SYSTEM SET aiven.pg_security_default_deny = on;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+DROP TABLE gk_drop_table;
+DROP FUNCTION gk_drop_function();
+DROP EXTENSION IF EXISTS gk_missing;
+-- statements that are always allowed
+SET search_path = public;
+RESET search_path;
+-- reserved roles are not affected
+SET SESSION AUTHORIZATION gk_admin;
+DROP TABLE gk_drop_table;
+DROP FUNCTION gk_drop_function();
+DROP EXTENSION IF EXISTS gk_missing;
+SET SESSION AUTHORIZATION gk_admin;
+ALTER SYSTEM RESET aiven.pg_security_default_deny;
+RESET SESSION AUTHORIZATION;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
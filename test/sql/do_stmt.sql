@@ -0,0 +1,15 @@
+-- DO in an untrusted language from an elevated context, a SECURITY DEFINER function owned by a superuser
+CREATE ROLE gk_user;
+CREATE FUNCTION gk_run_do(code text) RETURNS void LANGUAGE plpgsql SECURITY DEFINER AS $$
+BEGIN
+    EXECUTE code;
+END
+$$;
+SET SESSION AUTHORIZATION gk_user;
+SELECT gk_run_do('DO LANGUAGE plperlu ''1''');
+SELECT gk_run_do('DO LANGUAGE pltclu ''return''');
+-- trusted languages are not affected
+SELECT gk_run_do('DO $do$ BEGIN PERFORM 1; END $do$');
+RESET SESSION AUTHORIZATION;
+DROP FUNCTION gk_run_do(text);
+DROP ROLE gk_user;
@@ -0,0 +1,13 @@
+-- the extension and the schema it is installed in can only be dropped by reserved roles
+DROP EXTENSION aiven_gatekeeper;
+SET SESSION AUTHORIZATION gk_admin;
+CREATE SCHEMA gk_ext;
+ALTER EXTENSION aiven_gatekeeper SET SCHEMA gk_ext;
+RESET SESSION AUTHORIZATION;
+DROP SCHEMA gk_ext CASCADE;
+-- without CASCADE postgres refuses on its own
+DROP SCHEMA gk_ext;
+SET SESSION AUTHORIZATION gk_admin;
+DROP SCHEMA gk_ext CASCADE;
+CREATE EXTENSION aiven_gatekeeper SCHEMA public;
+RESET SESSION AUTHORIZATION;
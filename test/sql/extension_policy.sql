@@ -0,0 +1,32 @@
+-- the extensions are looked up after the checks, IF NOT EXISTS leaves the existing ones alone
+-- an entry allows the extension, all others are denied
+ALTER SYSTEM SET aiven.pg_security_extension_policy = 'plpgsql';
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+CREATE EXTENSION IF NOT EXISTS plpgsql;
+CREATE EXTENSION IF NOT EXISTS aiven_gatekeeper;
+-- * allows all others, !name excludes one
+ALTER SYSTEM SET aiven.pg_security_extension_policy = '*, !aiven_gatekeeper';
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+CREATE EXTENSION IF NOT EXISTS plpgsql;
+CREATE EXTENSION IF NOT EXISTS aiven_gatekeeper;
+-- !* denies all others
+ALTER SYSTEM SET aiven.pg_security_extension_policy = '!*, plpgsql';
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+CREATE EXTENSION IF NOT EXISTS plpgsql;
+CREATE EXTENSION IF NOT EXISTS aiven_gatekeeper;
+-- for the same name the negation wins, the order doesn't matter
+ALTER SYSTEM SET aiven.pg_security_extension_policy = '*, !plpgsql, plpgsql';
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+CREATE EXTENSION IF NOT EXISTS plpgsql;
+CREATE EXTENSION IF NOT EXISTS aiven_gatekeeper;
+-- the reserved roles are not limited by the policy outside of strict mode
+SET SESSION AUTHORIZATION gk_admin;
+CREATE EXTENSION IF NOT EXISTS plpgsql;
+ALTER SYSTEM RESET aiven.pg_security_extension_policy;
+RESET SESSION AUTHORIZATION;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
@@ -0,0 +1,66 @@
+-- SECURITY DEFINER functions created by a superuser run as that superuser
+ALTER SYSTEM SET aiven.pg_security_block_security_definer = on;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+CREATE ROLE gk_definer;
+GRANT CREATE ON SCHEMA public TO gk_definer;
+-- a normal role only hands out its own privileges
+SET SESSION AUTHORIZATION gk_definer;
+CREATE FUNCTION gk_definer_function() RETURNS int LANGUAGE sql SECURITY DEFINER AS 'SELECT 1';
+RESET SESSION AUTHORIZATION;
+CREATE FUNCTION gk_superuser_function() RETURNS int LANGUAGE sql SECURITY DEFINER AS 'SELECT 1';
+SET SESSION AUTHORIZATION gk_admin;
+CREATE FUNCTION gk_superuser_function() RETURNS int LANGUAGE sql SECURITY DEFINER AS 'SELECT 1';
+DROP FUNCTION gk_superuser_function();
+ALTER SYSTEM RESET aiven.pg_security_block_security_definer;
+RESET SESSION AUTHORIZATION;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+DROP FUNCTION gk_definer_function();
+REVOKE CREATE ON SCHEMA public FROM gk_definer;
+DROP ROLE gk_definer;
+-- replacing a function in a protected schema changes what trusted code does
+CREATE SCHEMA gk_protected;
+CREATE ROLE gk_app;
+GRANT USAGE, CREATE ON SCHEMA gk_protected TO gk_app;
+GRANT CREATE ON SCHEMA public TO gk_app;
+SET SESSION AUTHORIZATION gk_app;
+CREATE FUNCTION gk_protected.gk_trusted() RETURNS int LANGUAGE sql AS 'SELECT 1';
+CREATE FUNCTION public.gk_app_function() RETURNS int LANGUAGE sql AS 'SELECT 1';
+RESET SESSION AUTHORIZATION;
+ALTER SYSTEM SET aiven.pg_security_protected_schemas = 'pg_catalog, gk_protected';
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+SET SESSION AUTHORIZATION gk_app;
+CREATE OR REPLACE FUNCTION gk_protected.gk_trusted() RETURNS int LANGUAGE sql AS 'SELECT 2';
+-- outside of them a role replaces its own functions
+CREATE OR REPLACE FUNCTION public.gk_app_function() RETURNS int LANGUAGE sql AS 'SELECT 2';
+SET SESSION AUTHORIZATION gk_admin;
+CREATE OR REPLACE FUNCTION gk_protected.gk_trusted() RETURNS int LANGUAGE sql AS 'SELECT 2';
+ALTER SYSTEM RESET aiven.pg_security_protected_schemas;
+RESET SESSION AUTHORIZATION;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+DROP SCHEMA gk_protected CASCADE;
+DROP FUNCTION public.gk_app_function();
+REVOKE CREATE ON SCHEMA public FROM gk_app;
+DROP ROLE gk_app;
+-- the function denylist is only read at server start, not even the reserved roles can change it in a session
+SET aiven.pg_security_reserved_functions = '';
+SET SESSION AUTHORIZATION gk_admin;
+SET aiven.pg_security_reserved_functions = '';
+RESET aiven.pg_security_reserved_functions;
+SHOW aiven.pg_security_reserved_functions;
+RESET SESSION AUTHORIZATION;
+-- the execute hook runs in the middle of a query and only looks up the name in the catalog cache, never running
+-- SQL of its own, a call nested in a function is checked the same way as a direct one
+CREATE ROLE gk_caller;
+GRANT EXECUTE ON FUNCTION lo_import(text) TO gk_caller;
+CREATE FUNCTION gk_nested_import() RETURNS oid LANGUAGE plpgsql AS $$ BEGIN RETURN lo_import('/etc/passwd'); END $$;
+SET SESSION AUTHORIZATION gk_caller;
+SELECT lo_import('/etc/passwd');
+SELECT gk_nested_import();
+RESET SESSION AUTHORIZATION;
+DROP FUNCTION gk_nested_import();
+REVOKE EXECUTE ON FUNCTION lo_import(text) FROM gk_caller;
+DROP ROLE gk_caller;
@@ -0,0 +1,31 @@
+-- the reserved roles file and the additional reserved functions are reported with the other lists
+COPY (SELECT 'gk_file_role') TO '/tmp/gk_reserved_roles.conf';
+ALTER SYSTEM SET aiven.pg_security_config_file = '/tmp/gk_reserved_roles.conf';
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+SELECT list_name, entry FROM aiven_gatekeeper_lists() WHERE list_name LIKE 'reserved%' ORDER BY list_name, entry;
+-- the file is read again on every reload, the entries of the previous read are freed
+COPY (SELECT 'gk_file_role2') TO '/tmp/gk_reserved_roles.conf';
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+SELECT list_name, entry FROM aiven_gatekeeper_lists() WHERE list_name = 'reserved_roles_file' ORDER BY entry;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+SELECT list_name, entry FROM aiven_gatekeeper_lists() WHERE list_name = 'reserved_roles_file' ORDER BY entry;
+SET SESSION AUTHORIZATION gk_admin;
+ALTER SYSTEM RESET aiven.pg_security_config_file;
+RESET SESSION AUTHORIZATION;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+-- empty entries, trailing commas and duplicates are left out, unquoted names are lower case
+ALTER SYSTEM SET aiven.pg_security_critical_login_roles = ',gk_login,, GK_Login, "GK_Quoted", , gk_other,';
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+SELECT list_name, entry FROM aiven_gatekeeper_lists() WHERE list_name = 'critical_login_roles' ORDER BY entry COLLATE "C";
+SET SESSION AUTHORIZATION gk_admin;
+ALTER SYSTEM RESET aiven.pg_security_critical_login_roles;
+RESET SESSION AUTHORIZATION;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+-- the statement types with a check, named at registration
+SELECT * FROM aiven_gatekeeper_covered_statements();
@@ -0,0 +1,8 @@
+-- the denials name the object they are about
+CREATE EXTENSION file_fdw;
+CREATE ROLE gk_named SUPERUSER;
+-- COPY PROGRAM only names the command, the arguments can carry credentials
+COPY (SELECT 1) TO PROGRAM 'curl --user gk:secret https://example.com';
+COPY (SELECT 1) TO PROGRAM '/usr/bin/env  true';
+-- and long commands are cut
+COPY (SELECT 1) TO PROGRAM 'gk_xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx';
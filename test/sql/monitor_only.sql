@@ -0,0 +1,13 @@
+-- a monitor only build reports the denials as warnings and lets the statements run
+CREATE ROLE gk_monitored SUPERUSER;
+SELECT rolsuper FROM pg_roles WHERE rolname = 'gk_monitored';
+DROP ROLE gk_monitored;
+-- COPY PROGRAM runs as well, unless it is set to always be denied
+COPY (SELECT 1) TO PROGRAM 'cat > /dev/null';
+ALTER SYSTEM SET aiven.pg_security_copy_program_hard_block = on;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+COPY (SELECT 1) TO PROGRAM 'cat > /dev/null';
+ALTER SYSTEM RESET aiven.pg_security_copy_program_hard_block;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
@@ -0,0 +1,15 @@
+-- COPY can't be prepared, PREPARE only accepts plannable statements
+CREATE TABLE gk_copy_target (line text);
+PREPARE gk_copy AS COPY gk_copy_target FROM PROGRAM 'true';
+EXECUTE gk_copy;
+-- a COPY run dynamically still goes through the utility hook
+DO $$ BEGIN EXECUTE 'COPY gk_copy_target FROM PROGRAM ''true'''; END $$;
+-- also when the function running it is called from a prepared statement
+CREATE FUNCTION gk_copy_program() RETURNS void LANGUAGE plpgsql AS $$ BEGIN EXECUTE 'COPY gk_copy_target FROM PROGRAM ''true'''; END $$;
+PREPARE gk_copy AS SELECT gk_copy_program();
+EXECUTE gk_copy;
+DEALLOCATE gk_copy;
+DROP FUNCTION gk_copy_program();
+-- a program in the WITH (...) options is denied as well
+COPY gk_copy_target TO STDOUT WITH (FORMAT csv, PROGRAM 'true');
+DROP TABLE gk_copy_target;
@@ -0,0 +1,15 @@
+-- aiven_gatekeeper_reload() returns the reserved roles the reload puts in effect
+SET SESSION AUTHORIZATION gk_admin;
+ALTER SYSTEM SET aiven.pg_security_agent_reserved_roles = 'gk_admin, gk_reload_role';
+SELECT aiven_gatekeeper_reload();
+-- including the ones from the reserved roles file
+COPY (SELECT 'gk_file_reload_role') TO '/tmp/gk_reload_roles.conf';
+ALTER SYSTEM SET aiven.pg_security_config_file = '/tmp/gk_reload_roles.conf';
+SELECT aiven_gatekeeper_reload();
+SELECT pg_sleep(1);
+SELECT list_name, entry FROM aiven_gatekeeper_lists() WHERE list_name = 'reserved_roles' ORDER BY entry;
+ALTER SYSTEM RESET aiven.pg_security_agent_reserved_roles;
+ALTER SYSTEM RESET aiven.pg_security_config_file;
+SELECT aiven_gatekeeper_reload();
+RESET SESSION AUTHORIZATION;
+SELECT pg_sleep(1);
@@ -0,0 +1,28 @@
+-- file_fdw is blocked for everyone, the block leaves out whether it is installed
+DO $$ BEGIN CREATE EXTENSION file_fdw; DROP EXTENSION file_fdw; EXCEPTION WHEN undefined_file THEN NULL; END $$;
+SET SESSION AUTHORIZATION gk_admin;
+DO $$ BEGIN CREATE EXTENSION file_fdw; DROP EXTENSION file_fdw; EXCEPTION WHEN undefined_file THEN NULL; END $$;
+RESET SESSION AUTHORIZATION;
+-- the reserved roles can bypass the extension checks when it is listed
+ALTER SYSTEM SET aiven.pg_security_reserved_bypass = 'extension';
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+DO $$ BEGIN CREATE EXTENSION file_fdw; DROP EXTENSION file_fdw; EXCEPTION WHEN undefined_file THEN NULL; END $$;
+SET SESSION AUTHORIZATION gk_admin;
+DO $$ BEGIN CREATE EXTENSION file_fdw; DROP EXTENSION file_fdw; EXCEPTION WHEN undefined_file THEN NULL; END $$;
+RESET SESSION AUTHORIZATION;
+-- but not in strict mode
+ALTER SYSTEM SET aiven.pg_security_agent_strict_runtime = on;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+DO $$ BEGIN CREATE EXTENSION file_fdw; DROP EXTENSION file_fdw; EXCEPTION WHEN undefined_file THEN NULL; END $$;
+SET SESSION AUTHORIZATION gk_admin;
+DO $$ BEGIN CREATE EXTENSION file_fdw; DROP EXTENSION file_fdw; EXCEPTION WHEN undefined_file THEN NULL; END $$;
+ALTER SYSTEM SET aiven.pg_security_agent_strict_runtime = off;
+SELECT aiven_gatekeeper_reload();
+SELECT pg_sleep(1);
+ALTER SYSTEM RESET aiven.pg_security_agent_strict_runtime;
+ALTER SYSTEM RESET aiven.pg_security_reserved_bypass;
+RESET SESSION AUTHORIZATION;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
@@ -0,0 +1,47 @@
+-- ~regex entries reserve all roles following a naming convention
+ALTER SYSTEM SET aiven.pg_security_agent_reserved_roles = 'gk_admin, ~svc_.*_admin';
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+SELECT name, is_reserved FROM unnest(ARRAY['svc_billing_admin', 'svc_search_admin', 'svc_billing_reader', 'svc_admin']) AS name, aiven_gatekeeper_role_info(name);
+CREATE ROLE svc_search_admin;
+SET SESSION AUTHORIZATION gk_admin;
+CREATE ROLE svc_billing_admin SUPERUSER;
+CREATE ROLE svc_billing_reader SUPERUSER;
+-- the matching roles are reserved callers themselves
+SET SESSION AUTHORIZATION svc_billing_admin;
+CREATE EVENT TRIGGER gk_status ON ddl_command_start EXECUTE PROCEDURE gk_missing();
+SET SESSION AUTHORIZATION gk_admin;
+DROP ROLE svc_billing_admin;
+ALTER SYSTEM RESET aiven.pg_security_agent_reserved_roles;
+RESET SESSION AUTHORIZATION;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+-- db:role entries only reserve the role in that database, entries without a database everywhere
+ALTER SYSTEM SET aiven.pg_security_agent_reserved_roles = 'gk_admin, contrib_regression:gk_scoped, gk_other_db:gk_other, gk_global';
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+SELECT name, is_reserved FROM unnest(ARRAY['gk_scoped', 'gk_other', 'gk_global']) AS name, aiven_gatekeeper_role_info(name);
+SET SESSION AUTHORIZATION gk_admin;
+CREATE ROLE gk_scoped SUPERUSER;
+CREATE ROLE gk_other SUPERUSER;
+DROP ROLE gk_scoped;
+ALTER SYSTEM RESET aiven.pg_security_agent_reserved_roles;
+RESET SESSION AUTHORIZATION;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+-- a role named like a reserved role is treated as one, so it can't be created without the superuser attribute either
+ALTER SYSTEM SET aiven.pg_security_agent_reserved_roles = 'gk_admin, postgres';
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+CREATE ROLE gk_creator CREATEROLE;
+SET SESSION AUTHORIZATION gk_creator;
+CREATE ROLE postgres;
+CREATE ROLE postgres NOSUPERUSER NOINHERIT;
+CREATE ROLE gk_renamed;
+ALTER ROLE gk_renamed RENAME TO postgres;
+SET SESSION AUTHORIZATION gk_admin;
+ALTER SYSTEM RESET aiven.pg_security_agent_reserved_roles;
+RESET SESSION AUTHORIZATION;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+DROP ROLE gk_renamed, gk_creator;
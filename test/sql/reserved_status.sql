@@ -0,0 +1,24 @@
+-- the reserved status is kept for the current user, SET ROLE changes the user and resolves it again
+CREATE EVENT TRIGGER gk_status ON ddl_command_start EXECUTE PROCEDURE gk_missing();
+SET ROLE gk_admin;
+CREATE EVENT TRIGGER gk_status ON ddl_command_start EXECUTE PROCEDURE gk_missing();
+RESET ROLE;
+CREATE EVENT TRIGGER gk_status ON ddl_command_start EXECUTE PROCEDURE gk_missing();
+-- also inside one transaction
+BEGIN;
+SET LOCAL ROLE gk_admin;
+SAVEPOINT gk_reserved;
+CREATE EVENT TRIGGER gk_status ON ddl_command_start EXECUTE PROCEDURE gk_missing();
+ROLLBACK TO SAVEPOINT gk_reserved;
+RESET ROLE;
+CREATE EVENT TRIGGER gk_status ON ddl_command_start EXECUTE PROCEDURE gk_missing();
+ROLLBACK;
+-- and for SET SESSION AUTHORIZATION
+SET SESSION AUTHORIZATION gk_admin;
+CREATE EVENT TRIGGER gk_status ON ddl_command_start EXECUTE PROCEDURE gk_missing();
+RESET SESSION AUTHORIZATION;
+CREATE EVENT TRIGGER gk_status ON ddl_command_start EXECUTE PROCEDURE gk_missing();
+-- the statements issued from inside a DO block or function don't get the exemptions of the reserved roles
+SET SESSION AUTHORIZATION gk_admin;
+DO $$ BEGIN CREATE EVENT TRIGGER gk_status ON ddl_command_start EXECUTE PROCEDURE gk_missing(); END $$;
+RESET SESSION AUTHORIZATION;
@@ -0,0 +1,31 @@
+-- in strict mode only the protected role attributes can't be changed, also by the reserved roles
+CREATE ROLE gk_attr;
+ALTER SYSTEM SET aiven.pg_security_agent_reserved_roles = 'gk_admin, gk_rotated';
+ALTER SYSTEM SET aiven.pg_security_protected_role_attributes = 'superuser, createdb, connection_limit';
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+SET SESSION AUTHORIZATION gk_admin;
+CREATE ROLE gk_rotated LOGIN;
+ALTER SYSTEM SET aiven.pg_security_agent_strict_runtime = on;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+ALTER ROLE gk_attr CREATEDB;
+ALTER ROLE gk_attr CONNECTION LIMIT 5;
+-- turning an attribute off doesn't protect anything, the attributes not in the list are not protected
+ALTER ROLE gk_attr NOCREATEDB;
+ALTER ROLE gk_attr REPLICATION BYPASSRLS;
+-- the passwords of the reserved roles are rotated out of band in strict mode
+ALTER ROLE gk_rotated PASSWORD 'SCRAM-SHA-256$4096:Z2tfcmVncmVzc19zYWx0IQ==$wNZz6/FzKyY7oDJpZbCZ4NukMD/XqKu3acgY9gUsG9M=:z3tMG4gORVPsYYuzmIfvL92v5dI2z0ayYgLHMsBgacE=';
+ALTER ROLE gk_rotated PASSWORD NULL;
+ALTER ROLE gk_attr PASSWORD 'SCRAM-SHA-256$4096:Z2tfcmVncmVzc19zYWx0IQ==$wNZz6/FzKyY7oDJpZbCZ4NukMD/XqKu3acgY9gUsG9M=:z3tMG4gORVPsYYuzmIfvL92v5dI2z0ayYgLHMsBgacE=';
+ALTER SYSTEM SET aiven.pg_security_agent_strict_runtime = off;
+SELECT aiven_gatekeeper_reload();
+SELECT pg_sleep(1);
+DROP ROLE gk_rotated;
+ALTER SYSTEM RESET aiven.pg_security_agent_strict_runtime;
+ALTER SYSTEM RESET aiven.pg_security_agent_reserved_roles;
+ALTER SYSTEM RESET aiven.pg_security_protected_role_attributes;
+RESET SESSION AUTHORIZATION;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+DROP ROLE gk_attr;
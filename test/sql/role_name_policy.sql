@@ -0,0 +1,20 @@
+-- without aiven.pg_security_role_name_pattern any name can be used
+CREATE ROLE gk_unprefixed;
+DROP ROLE gk_unprefixed;
+-- with the pattern, the roles created or renamed by non-reserved roles have to match it
+ALTER SYSTEM SET aiven.pg_security_role_name_pattern = 'app_.*';
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+CREATE ROLE app_orders;
+CREATE ROLE gk_unprefixed;
+-- the pattern has to match the whole name
+CREATE ROLE my_app_orders;
+ALTER ROLE app_orders RENAME TO orders;
+-- the reserved roles are exempt
+SET SESSION AUTHORIZATION gk_admin;
+CREATE ROLE gk_managed;
+ALTER SYSTEM RESET aiven.pg_security_role_name_pattern;
+RESET SESSION AUTHORIZATION;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+DROP ROLE app_orders, gk_managed;
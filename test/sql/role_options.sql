@@ -0,0 +1,15 @@
+-- a role statement with more options than aiven.pg_security_max_role_options is denied before the options are checked
+ALTER SYSTEM SET aiven.pg_security_max_role_options = 2;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+CREATE ROLE gk_options LOGIN CREATEDB NOINHERIT;
+CREATE ROLE gk_options LOGIN CREATEDB;
+ALTER ROLE gk_options NOLOGIN NOCREATEDB CONNECTION LIMIT 1;
+ALTER ROLE gk_options NOLOGIN NOCREATEDB;
+SELECT rolcanlogin, rolcreatedb FROM pg_roles WHERE rolname = 'gk_options';
+DROP ROLE gk_options;
+SET SESSION AUTHORIZATION gk_admin;
+ALTER SYSTEM RESET aiven.pg_security_max_role_options;
+RESET SESSION AUTHORIZATION;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
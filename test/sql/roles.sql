@@ -0,0 +1,90 @@
+-- a role with CREATEROLE can't give itself the privileged attributes
+CREATE ROLE gk_self CREATEROLE;
+SET SESSION AUTHORIZATION gk_self;
+ALTER ROLE current_user SUPERUSER;
+ALTER ROLE current_user REPLICATION;
+ALTER ROLE current_user BYPASSRLS;
+-- the reserved roles can
+SET SESSION AUTHORIZATION gk_admin;
+ALTER ROLE current_user SUPERUSER;
+RESET SESSION AUTHORIZATION;
+DROP ROLE gk_self;
+-- IN ROLE grants the membership at creation time, same as GRANT ROLE
+CREATE ROLE gk_member IN ROLE gk_admin;
+CREATE ROLE gk_member IN ROLE pg_read_server_files;
+-- with the password policy, passwords have to be sent encrypted
+ALTER SYSTEM SET aiven.pg_security_enforce_password_policy = on;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+CREATE ROLE gk_password PASSWORD 'plain';
+CREATE ROLE gk_password PASSWORD 'SCRAM-SHA-256$4096:Z2tfcmVncmVzc19zYWx0IQ==$wNZz6/FzKyY7oDJpZbCZ4NukMD/XqKu3acgY9gUsG9M=:z3tMG4gORVPsYYuzmIfvL92v5dI2z0ayYgLHMsBgacE=';
+ALTER ROLE gk_password PASSWORD 'plain';
+ALTER ROLE gk_password PASSWORD NULL;
+-- and only the reserved roles set the password of a reserved role
+ALTER ROLE gk_admin PASSWORD 'SCRAM-SHA-256$4096:Z2tfcmVncmVzc19zYWx0IQ==$wNZz6/FzKyY7oDJpZbCZ4NukMD/XqKu3acgY9gUsG9M=:z3tMG4gORVPsYYuzmIfvL92v5dI2z0ayYgLHMsBgacE=';
+SET SESSION AUTHORIZATION gk_admin;
+ALTER ROLE gk_admin PASSWORD 'SCRAM-SHA-256$4096:Z2tfcmVncmVzc19zYWx0IQ==$wNZz6/FzKyY7oDJpZbCZ4NukMD/XqKu3acgY9gUsG9M=:z3tMG4gORVPsYYuzmIfvL92v5dI2z0ayYgLHMsBgacE=';
+ALTER SYSTEM RESET aiven.pg_security_enforce_password_policy;
+RESET SESSION AUTHORIZATION;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+DROP ROLE gk_password;
+-- the server access roles can only be granted to the reserved roles
+CREATE ROLE gk_grantee;
+GRANT pg_execute_server_program TO gk_grantee;
+GRANT pg_execute_server_program TO gk_admin;
+REVOKE pg_execute_server_program FROM gk_admin;
+DROP ROLE gk_grantee;
+-- all violations of one statement are reported together
+CREATE ROLE gk_multi SUPERUSER IN ROLE pg_execute_server_program, pg_read_server_files;
+-- REASSIGN OWNED and DROP OWNED of the reserved roles only for the reserved roles
+CREATE ROLE gk_owner;
+REASSIGN OWNED BY gk_owner TO gk_admin;
+REASSIGN OWNED BY gk_admin TO gk_owner;
+DROP OWNED BY gk_admin;
+SET SESSION AUTHORIZATION gk_admin;
+REASSIGN OWNED BY gk_owner TO gk_admin;
+RESET SESSION AUTHORIZATION;
+DROP ROLE gk_owner;
+-- disabling LOGIN of a critical role only for the reserved roles
+ALTER SYSTEM SET aiven.pg_security_critical_login_roles = 'gk_replicator';
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+CREATE ROLE gk_replicator LOGIN;
+ALTER ROLE gk_replicator NOLOGIN;
+ALTER ROLE gk_replicator LOGIN;
+SET SESSION AUTHORIZATION gk_admin;
+ALTER ROLE gk_replicator NOLOGIN;
+ALTER SYSTEM RESET aiven.pg_security_critical_login_roles;
+RESET SESSION AUTHORIZATION;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+DROP ROLE gk_replicator;
+-- the guarded attributes of a reserved role only change for the reserved roles, in both directions
+ALTER SYSTEM SET aiven.pg_security_guarded_role_attributes = 'inherit';
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+ALTER ROLE gk_admin NOINHERIT;
+ALTER ROLE gk_admin INHERIT;
+SET SESSION AUTHORIZATION gk_admin;
+ALTER ROLE gk_admin NOINHERIT;
+ALTER ROLE gk_admin INHERIT;
+ALTER SYSTEM RESET aiven.pg_security_guarded_role_attributes;
+RESET SESSION AUTHORIZATION;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+-- ADMIN OPTION on a reserved role only goes to the reserved roles, CREATE ROLE ... ADMIN makes the listed roles admins of the new role
+ALTER SYSTEM SET aiven.pg_security_agent_reserved_roles = 'gk_admin, gk_admin_of';
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+SET SESSION AUTHORIZATION gk_admin;
+CREATE ROLE gk_admin_of ADMIN postgres;
+CREATE ROLE gk_admin_of ADMIN gk_admin;
+CREATE ROLE gk_plain;
+GRANT gk_admin_of TO gk_plain WITH ADMIN OPTION;
+GRANT gk_admin_of TO gk_plain;
+ALTER SYSTEM RESET aiven.pg_security_agent_reserved_roles;
+RESET SESSION AUTHORIZATION;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+DROP ROLE gk_plain, gk_admin_of;
@@ -0,0 +1,21 @@
+-- aiven.pg_security_sensitive_columns entries are schema.table.column, unquoted names are folded to lower case
+ALTER SYSTEM SET aiven.pg_security_sensitive_columns = 'public.gk_secrets';
+ALTER SYSTEM SET aiven.pg_security_sensitive_columns = 'public..token';
+ALTER SYSTEM SET aiven.pg_security_sensitive_columns = 'public.gk_secrets.token, Public.GK_Other.Token';
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+SELECT entry FROM aiven_gatekeeper_lists() WHERE list_name = 'sensitive_columns' ORDER BY entry COLLATE "C";
+-- COPY TO of a sensitive column is denied, also without a column list
+CREATE TABLE gk_secrets (id int, token text);
+COPY gk_secrets TO STDOUT;
+COPY gk_secrets (id, token) TO STDOUT;
+COPY gk_secrets (id) TO STDOUT;
+-- the reserved roles can copy them
+SET SESSION AUTHORIZATION gk_admin;
+COPY gk_secrets TO STDOUT;
+ALTER SYSTEM RESET aiven.pg_security_sensitive_columns;
+RESET SESSION AUTHORIZATION;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+COPY gk_secrets TO STDOUT;
+DROP TABLE gk_secrets;
@@ -0,0 +1,20 @@
+-- the metrics file is replaced by the metrics worker, it can't be a file of the server
+ALTER SYSTEM SET aiven.pg_security_metrics_file = 'pg_hba.conf';
+ALTER SYSTEM SET aiven.pg_security_metrics_file = '/tmp/gk_metrics.prom';
+-- only the reserved roles can remove the persisted agent settings
+ALTER SYSTEM RESET aiven.pg_security_metrics_file;
+SET SESSION AUTHORIZATION gk_admin;
+ALTER SYSTEM RESET aiven.pg_security_metrics_file;
+RESET SESSION AUTHORIZATION;
+-- the notify database only takes effect after a restart, it is still validated when it is stored
+ALTER SYSTEM SET aiven.pg_security_denial_notify_database = '';
+ALTER SYSTEM SET aiven.pg_security_denial_notify_database = 'gk_notify';
+ALTER SYSTEM SET aiven.pg_security_denial_notify_database TO DEFAULT;
+SET SESSION AUTHORIZATION gk_admin;
+ALTER SYSTEM SET aiven.pg_security_denial_notify_database TO DEFAULT;
+RESET SESSION AUTHORIZATION;
+-- RESET ALL also removes the agent settings
+ALTER SYSTEM RESET ALL;
+SET SESSION AUTHORIZATION gk_admin;
+ALTER SYSTEM RESET ALL;
+RESET SESSION AUTHORIZATION;
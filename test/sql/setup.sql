@@ -0,0 +1,11 @@
+-- shared by the other tests, the bootstrap superuser running them is not a reserved role, gk_admin is
+-- only a reserved role can create a role with a reserved role name, the bootstrap superuser is one while it does
+SELECT format('ALTER SYSTEM SET aiven.pg_security_agent_reserved_roles = %L', 'gk_admin, ' || quote_ident(session_user)) AS reserve_bootstrap \gset
+:reserve_bootstrap;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+CREATE ROLE gk_admin SUPERUSER;
+ALTER SYSTEM RESET aiven.pg_security_agent_reserved_roles;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+CREATE EXTENSION aiven_gatekeeper;
@@ -0,0 +1,40 @@
+-- strict mode, turned on at runtime and applied with a reload
+ALTER SYSTEM SET aiven.pg_security_agent_strict_runtime = on;
+SELECT pg_reload_conf();
+SELECT pg_sleep(1);
+SHOW aiven.pg_security_agent_strict_runtime;
+SELECT strict_mode FROM aiven_gatekeeper_context();
+-- untrusted languages, denied before the language is looked up
+CREATE FUNCTION gk_untrusted() RETURNS void LANGUAGE plperlu AS '1';
+CREATE FUNCTION gk_untrusted() RETURNS void LANGUAGE plpython3u AS 'pass';
+CREATE FUNCTION gk_untrusted() RETURNS void LANGUAGE pltclu AS 'return';
+DO LANGUAGE plpythonu 'pass';
+DO LANGUAGE plperlu '1';
+-- trusted languages are not affected
+DO $$ BEGIN PERFORM 1; END $$;
+-- monitored builtin functions
+CREATE FUNCTION gk_read_file(text) RETURNS text LANGUAGE internal AS 'pg_read_file_all';
+-- file access, also for a reserved role
+COPY (SELECT 1) TO '/tmp/gk_strict_mode.out';
+SET SESSION AUTHORIZATION gk_admin;
+COPY (SELECT 1) TO '/tmp/gk_strict_mode.out';
+-- large objects read and write server files, lo_import and lo_export are monitored builtins
+SELECT lo_import('/etc/passwd');
+SELECT lo_import('/etc/passwd', 424242);
+SELECT lo_export(424242, '/tmp/gk_strict_mode.out');
+-- even for a role that is in the reserved roles list
+CREATE ROLE gk_admin SUPERUSER;
+RESET SESSION AUTHORIZATION;
+-- back to normal, turning strict mode off is a configuration change like any other
+ALTER SYSTEM SET aiven.pg_security_agent_strict_runtime = off;
+-- pg_reload_conf() is one of the monitored builtin functions
+SELECT pg_reload_conf();
+SET SESSION AUTHORIZATION gk_admin;
+SELECT aiven_gatekeeper_reload();
+RESET SESSION AUTHORIZATION;
+SELECT pg_sleep(1);
+SHOW aiven.pg_security_agent_strict_runtime;
+SELECT strict_mode FROM aiven_gatekeeper_context();
+SET SESSION AUTHORIZATION gk_admin;
+ALTER SYSTEM RESET aiven.pg_security_agent_strict_runtime;
+RESET SESSION AUTHORIZATION;
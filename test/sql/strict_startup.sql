@@ -0,0 +1,11 @@
+-- strict mode on at server start with no reserved roles falls back to postgres, with a warning in the server log
+CREATE EXTENSION aiven_gatekeeper;
+SELECT list_name, entry FROM aiven_gatekeeper_lists() WHERE list_name = 'reserved_roles';
+SELECT is_reserved FROM aiven_gatekeeper_role_info('postgres');
+-- the reserved roles can't be changed in strict mode
+ALTER SYSTEM SET aiven.pg_security_agent_reserved_roles = 'gk_admin';
+-- strict mode is on when either setting is, the runtime setting can't turn it off
+SHOW aiven.pg_security_agent_strict_runtime;
+SELECT strict_mode FROM aiven_gatekeeper_context();
+COPY (SELECT 1) TO '/tmp/gk_strict_startup.out';
+ALTER SYSTEM SET aiven.pg_security_agent_strict_runtime = off;
@@ -0,0 +1,15 @@
+-- a make GATEKEEPER_TEST_API=1 build runs statements built in memory through the checks without executing them
+CREATE FUNCTION aiven_gatekeeper_test_create_role(rolename text, option text, value text)
+RETURNS boolean AS 'aiven_gatekeeper', 'aiven_gatekeeper_test_create_role' LANGUAGE C;
+CREATE FUNCTION aiven_gatekeeper_test_copy(relname text, filename text, is_from boolean, is_program boolean)
+RETURNS boolean AS 'aiven_gatekeeper', 'aiven_gatekeeper_test_copy' LANGUAGE C;
+-- a denial raises the same error as the real statement
+SELECT aiven_gatekeeper_test_create_role('gk_test_api', 'superuser', NULL);
+SELECT aiven_gatekeeper_test_copy('gk_test_api', 'cat > /dev/null', false, true);
+-- otherwise the checks pass and nothing is executed
+SELECT aiven_gatekeeper_test_create_role('gk_test_api', 'createdb', NULL);
+SELECT aiven_gatekeeper_test_create_role('gk_test_api', NULL, NULL);
+SELECT aiven_gatekeeper_test_copy('gk_test_api', NULL, false, false);
+SELECT count(*) FROM pg_roles WHERE rolname = 'gk_test_api';
+DROP FUNCTION aiven_gatekeeper_test_create_role(text, text, text);
+DROP FUNCTION aiven_gatekeeper_test_copy(text, text, boolean, boolean);
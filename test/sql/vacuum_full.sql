@@ -0,0 +1,15 @@
+-- rewriting the catalogs is only allowed for reserved roles
+VACUUM FULL pg_authid;
+VACUUM FULL pg_catalog.pg_authid;
+CLUSTER pg_authid USING pg_authid_oid_index;
+-- without a table every table is rewritten, for a superuser including the catalogs
+VACUUM FULL;
+VACUUM (FULL);
+-- a plain VACUUM doesn't rewrite the table
+VACUUM pg_authid;
+CREATE TABLE gk_vacuum (id int);
+VACUUM FULL gk_vacuum;
+SET SESSION AUTHORIZATION gk_admin;
+VACUUM FULL pg_authid;
+RESET SESSION AUTHORIZATION;
+DROP TABLE gk_vacuum;